@@ -1,10 +1,157 @@
 extends Node2D
 
+const MULTI_PV_LINES := 3
+
+var game
+
+@onready var status_label := get_node("StatusLabel")
+@onready var hint_button := get_node("HintButton")
+@onready var analyse_button := get_node("AnalyseButton")
+@onready var eval_graph := get_node("EvalGraph")
+@onready var analysis_label := get_node("AnalysisLabel")
+@onready var multi_pv_panel := get_node("MultiPvPanel")
+@onready var multi_pv_stats_label := get_node("MultiPvPanel/StatsLabel")
+var multi_pv_buttons := []
+
 
 func _ready():
-	var game = GodotGame.new();
+	game = GodotGame.new();
 	add_child(game);
-	if Globals.fen_string == "":
-		game.start(Globals.player_color)
+	move_child(game, 0)
+	game.set_accessibility_mode(Globals.accessibility_mode)
+	game.move_played.connect(_on_move_played)
+	game.game_over.connect(_on_game_over)
+	game.turn_changed.connect(func(_color): _update_hint_button())
+	game.hint_shown.connect(func(_san, _uci, _score): _update_hint_button())
+	game.turn_changed.connect(func(_color): _hide_analysis())
+	game.analysis_progress.connect(_on_analysis_progress)
+	game.analysis_finished.connect(_on_analysis_finished)
+	game.opponent_info.connect(func(text): analysis_label.set_text(text))
+	game.opponent_failed.connect(func(reason): analysis_label.set_text("Opponent failed: %s" % reason))
+	game.book_move_played.connect(func(_uci): status_label.set_text("%s (book)" % status_label.get_text()))
+	var error = game.new_game(Globals.game_config)
+	if error != "":
+		push_error(error)
+		get_tree().change_scene_to_file.call_deferred("res://menu/menu.tscn")
+		return
+	Globals.settings_changed.connect(_on_settings_changed)
+	_update_hint_button()
+	if Globals.game_config.analysis:
+		_init_multi_pv_panel()
+
+
+func _init_multi_pv_panel():
+	for index in MULTI_PV_LINES:
+		var button = Button.new()
+		button.alignment = HORIZONTAL_ALIGNMENT_LEFT
+		button.clip_text = true
+		button.mouse_entered.connect(func(): game.preview_line(index))
+		button.mouse_exited.connect(func(): game.clear_line_preview())
+		button.pressed.connect(func(): game.play_line(index))
+		multi_pv_panel.add_child(button)
+		multi_pv_buttons.append(button)
+	game.multi_pv_updated.connect(_on_multi_pv_updated)
+	game.turn_changed.connect(func(_color): _clear_multi_pv_lines())
+	multi_pv_panel.show()
+	game.start_multi_pv(MULTI_PV_LINES)
+
+
+func _clear_multi_pv_lines():
+	# Also brings the panel back when a finished game is taken back
+	multi_pv_panel.show()
+	multi_pv_stats_label.set_text("Searching...")
+	for button in multi_pv_buttons:
+		button.hide()
+
+
+func _on_multi_pv_updated(lines: Array, depth: int, nodes: int, nodes_per_second: int, elapsed_ms: int):
+	multi_pv_stats_label.set_text(
+		"Depth %d, %d nodes, %d nps, %.1f s" % [depth, nodes, nodes_per_second, elapsed_ms / 1000.0]
+	)
+	for index in multi_pv_buttons.size():
+		var button = multi_pv_buttons[index]
+		if index >= lines.size():
+			button.hide()
+			continue
+		var line = lines[index]
+		button.set_text("%s (d%d) %s" % [line.eval, line.depth, " ".join(line.san)])
+		button.show()
+
+
+func _on_move_played(san: String, _uci: String, _is_capture: bool, _is_check: bool):
+	status_label.set_text(san)
+
+
+func _on_game_over(result: String, reason: String):
+	status_label.set_text("%s (%s)" % [result, reason])
+	hint_button.disabled = true
+	analyse_button.show()
+	multi_pv_panel.hide()
+
+
+func _hide_analysis():
+	analyse_button.hide()
+	analyse_button.disabled = false
+	eval_graph.hide()
+	analysis_label.set_text("")
+
+
+func _on_analyse_button_pressed() -> void:
+	analyse_button.disabled = true
+	analysis_label.set_text("Analysing...")
+	game.analyse_game()
+
+
+func _on_analysis_progress(done: int, total: int):
+	analysis_label.set_text("Analysing... %d/%d" % [done, total])
+
+
+func _on_analysis_finished():
+	analyse_button.hide()
+	eval_graph.set_evaluations(game.get_analysis_evaluations())
+	eval_graph.show()
+	var counts = {}
+	for ply in game.get_analysis():
+		counts[ply.classification] = counts.get(ply.classification, 0) + 1
+	analysis_label.set_text("Inaccuracies: %d, mistakes: %d, blunders: %d" % [
+		counts.get("inaccuracy", 0), counts.get("mistake", 0), counts.get("blunder", 0)])
+
+
+func _on_eval_graph_ply_clicked(ply: int):
+	game.show_ply(ply)
+	if ply == 0:
+		analysis_label.set_text("Starting position")
+		return
+	var analysis = game.get_analysis()[ply - 1]
+	var evaluation = analysis.tablebase if analysis.tablebase != "" else "%+.2f" % analysis.white_eval
+	var text = "%s is %s (%s)" % [analysis.san, analysis.classification, evaluation]
+	if analysis.better_move != "":
+		text += ", better was %s" % analysis.better_move
+	analysis_label.set_text(text)
+
+
+func _update_hint_button():
+	var hints_left = game.get_hints_left()
+	if hints_left < 0:
+		hint_button.set_text("Hint")
 	else:
-		game.start_from_fen(Globals.fen_string)
+		hint_button.set_text("Hint (%d left)" % hints_left)
+	hint_button.disabled = hints_left == 0 or game.is_game_over() or game.is_hint_pending()
+
+
+func _on_hint_button_pressed() -> void:
+	game.request_hint()
+	_update_hint_button()
+
+
+func _on_settings_changed():
+	game.apply_settings(Globals.settings)
+
+
+func _on_options_button_pressed() -> void:
+	var options = load("res://menu/options.tscn").instantiate()
+	options.overlay = true
+	# The board reads raw input, so keep clicks on the overlay from reaching it
+	game.set_process_input(false)
+	options.tree_exited.connect(func(): game.set_process_input(true))
+	add_child(options)
@@ -1,4 +1,24 @@
 extends Node
 
-var player_color = "white";
-var fen_string = "";
+signal settings_changed
+
+var accessibility_mode = false;
+var game_config = GameConfig.new();
+var settings = GodotSettings.load_settings();
+
+
+func _ready():
+	ChessPosition.warm_up_engine()
+
+
+# Every screen that starts a game builds its config here, so the player's settings all make it into the game
+func new_game_config(side: String, opponent: String, rated: bool) -> GameConfig:
+	var config = GameConfig.new()
+	config.side = side
+	config.opponent = opponent
+	config.bot_level = settings.bot_level
+	config.blunder_chance = settings.blunder_chance
+	config.uci_engine_path = settings.uci_engine_path
+	# Only games against the computer can be rated
+	config.rated = opponent != "human" and rated
+	return config
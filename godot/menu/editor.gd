@@ -0,0 +1,99 @@
+extends Node2D
+
+const PLAYER_SIDES = ["white", "black", "random"]
+
+@onready var player_side_option := get_node("Controls/BottomRow/PlayerSideOption")
+@onready var side_option := get_node("Controls/TopRow/SideOption")
+@onready var white_short_check := get_node("Controls/TopRow/WhiteShortCheck")
+@onready var white_long_check := get_node("Controls/TopRow/WhiteLongCheck")
+@onready var black_short_check := get_node("Controls/TopRow/BlackShortCheck")
+@onready var black_long_check := get_node("Controls/TopRow/BlackLongCheck")
+@onready var en_passant_input := get_node("Controls/TopRow/EnPassantInput")
+@onready var error_label := get_node("Controls/ErrorLabel")
+
+var editor
+
+
+func _ready():
+	editor = GodotPositionEditor.new()
+	add_child(editor)
+	move_child(editor, 0)
+	editor.custom_ready()
+	if Globals.game_config.fen != "":
+		editor.load_fen(Globals.game_config.fen)
+	player_side_option.select(max(PLAYER_SIDES.find(Globals.game_config.side), 0))
+	_sync_controls()
+
+
+func _sync_controls():
+	side_option.select(0 if editor.get_side_to_move() == "white" else 1)
+	var rights = editor.get_castling_rights()
+	white_short_check.set_pressed_no_signal(rights.contains("K"))
+	white_long_check.set_pressed_no_signal(rights.contains("Q"))
+	black_short_check.set_pressed_no_signal(rights.contains("k"))
+	black_long_check.set_pressed_no_signal(rights.contains("q"))
+	en_passant_input.set_text(editor.get_en_passant())
+	error_label.set_text("")
+
+
+func _apply_controls() -> bool:
+	editor.set_side_to_move("white" if side_option.get_selected() == 0 else "black")
+	var rights = ""
+	if white_short_check.is_pressed():
+		rights += "K"
+	if white_long_check.is_pressed():
+		rights += "Q"
+	if black_short_check.is_pressed():
+		rights += "k"
+	if black_long_check.is_pressed():
+		rights += "q"
+	editor.set_castling_rights(rights)
+	if not editor.set_en_passant(en_passant_input.get_text().strip_edges()):
+		error_label.set_text("En passant square should look like e3")
+		return false
+
+	var problem = editor.validate()
+	error_label.set_text(problem)
+	return problem == ""
+
+
+func _start(analysis: bool):
+	if not _apply_controls():
+		return
+	var side = PLAYER_SIDES[player_side_option.get_selected()]
+	var rated = Globals.game_config.rated and not analysis
+	var config = Globals.new_game_config(side, Globals.game_config.opponent, rated)
+	config.fen = editor.get_fen()
+	config.analysis = analysis
+	var problem = config.validate()
+	if problem != "":
+		error_label.set_text(problem)
+		return
+	Globals.game_config = config
+	get_tree().change_scene_to_file("res://chess_game.tscn")
+
+
+func _on_play_button_pressed() -> void:
+	_start(false)
+
+
+func _on_analyse_button_pressed() -> void:
+	_start(true)
+
+
+func _on_flip_button_pressed() -> void:
+	editor.flip()
+
+
+func _on_clear_button_pressed() -> void:
+	editor.clear()
+	_sync_controls()
+
+
+func _on_start_position_button_pressed() -> void:
+	editor.set_start_position()
+	_sync_controls()
+
+
+func _on_back_button_pressed() -> void:
+	get_tree().change_scene_to_file("res://menu/menu.tscn")
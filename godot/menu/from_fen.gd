@@ -1,16 +1,23 @@
 extends Node2D
 
+const PLAYER_SIDES = ["white", "black", "random"]
+
 @onready var text_input := get_node("CenterContainer/VBoxContainer/FenInput")
+@onready var player_side_option := get_node("CenterContainer/VBoxContainer/PlayerSideOption")
+
+
+func _ready():
+	player_side_option.select(max(PLAYER_SIDES.find(Globals.game_config.side), 0))
 
 
 func _on_start_button_pressed() -> void:
 	var fen_string = text_input.get_text()
-	var game = GodotGame.new()
-	game.check_fen_string(fen_string)
-	
-	if game.check_fen_string(fen_string):
-		game.free()
-		Globals.fen_string = fen_string
+	if ChessPosition.is_valid_fen(fen_string):
+		# The side to move is in the FEN, the player may still take either side
+		var side = PLAYER_SIDES[player_side_option.get_selected()]
+		var config = Globals.new_game_config(side, Globals.game_config.opponent, Globals.game_config.rated)
+		config.fen = fen_string
+		Globals.game_config = config
 		get_tree().change_scene_to_file("res://chess_game.tscn")
 
 
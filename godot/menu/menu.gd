@@ -1,19 +1,52 @@
 extends Node
 
-func _start_game():
+const OPPONENTS = ["human", "bot", "uci", "random", "greedy", "cautious", "blunderer"]
+
+@onready var accessibility_check_button := get_node("VBoxContainer/AccessibilityCheckButton")
+@onready var opponent_option := get_node("VBoxContainer/OpponentOption")
+@onready var rated_check := get_node("VBoxContainer/RatedCheck")
+@onready var error_label := get_node("VBoxContainer/ErrorLabel")
+
+func _ready():
+	accessibility_check_button.set_pressed_no_signal(Globals.accessibility_mode)
+	opponent_option.select(max(OPPONENTS.find(Globals.game_config.opponent), 0))
+	rated_check.set_pressed_no_signal(Globals.game_config.rated)
+	_on_opponent_option_item_selected(opponent_option.get_selected())
+	var engine_error = ChessPosition.get_finished_engine_error()
+	if engine_error != "":
+		error_label.set_text(engine_error)
+		for button_name in ["HBoxContainer/StartButtonWhite", "HBoxContainer/StartButtonBlack", "StartButtonFen", "EditorButton"]:
+			get_node("VBoxContainer/" + button_name).set_disabled(true)
+
+func _start_game(side: String):
+	var config = Globals.new_game_config(side, OPPONENTS[opponent_option.get_selected()], rated_check.is_pressed())
+	# Reported here rather than after switching scenes, e.g. when no UCI engine is set up
+	var problem = config.validate()
+	if problem != "":
+		error_label.set_text(problem)
+		return
+	Globals.game_config = config
 	get_tree().change_scene_to_file("res://chess_game.tscn")
 
 func _on_start_button_white_pressed() -> void:
-	Globals.player_color = "white";
-	_start_game()
+	_start_game("white")
 
 func _on_start_button_black_pressed() -> void:
-	Globals.player_color = "black";
-	_start_game()
+	_start_game("black")
+
+func _on_opponent_option_item_selected(index: int) -> void:
+	# Only games against the computer can be rated
+	rated_check.set_disabled(index == 0)
 
 func _on_start_button_fen_pressed() -> void:
 	get_tree().change_scene_to_file("res://menu/from_fen.tscn")
 
+func _on_editor_button_pressed() -> void:
+	get_tree().change_scene_to_file("res://menu/editor.tscn")
+
+func _on_accessibility_check_button_toggled(toggled_on: bool) -> void:
+	Globals.accessibility_mode = toggled_on
+
 func _on_quit_button_pressed() -> void:
 	get_tree().quit(0)
 
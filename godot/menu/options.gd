@@ -1,5 +1,157 @@
 extends Node2D
 
+const THEMES = ["classic", "green", "blue", "high_contrast"]
+const TIME_CONTROLS = ["none", "1+0", "3+2", "5+0", "10+5", "15+10"]
+const HINT_LIMITS = [0, 1, 3, 5, -1]
+
+## Set when opened on top of a running game, so Back closes the overlay instead of switching scenes
+var overlay := false
+
+@onready var theme_option := get_node("CenterContainer/VBoxContainer/ThemeOption")
+@onready var volume_slider := get_node("CenterContainer/VBoxContainer/VolumeSlider")
+@onready var mute_check := get_node("CenterContainer/VBoxContainer/MuteCheck")
+@onready var animation_slider := get_node("CenterContainer/VBoxContainer/AnimationSlider")
+@onready var legal_moves_check := get_node("CenterContainer/VBoxContainer/LegalMovesCheck")
+@onready var auto_queen_check := get_node("CenterContainer/VBoxContainer/AutoQueenCheck")
+@onready var bot_level_spin := get_node("CenterContainer/VBoxContainer/BotLevelSpin")
+@onready var blunder_chance_slider := get_node("CenterContainer/VBoxContainer/BlunderChanceSlider")
+@onready var uci_engine_path_edit := get_node("CenterContainer/VBoxContainer/UciEnginePathEdit")
+@onready var book_path_edit := get_node("CenterContainer/VBoxContainer/BookPathEdit")
+@onready var book_depth_spin := get_node("CenterContainer/VBoxContainer/BookDepthSpin")
+@onready var book_variety_slider := get_node("CenterContainer/VBoxContainer/BookVarietySlider")
+@onready var syzygy_path_edit := get_node("CenterContainer/VBoxContainer/SyzygyPathEdit")
+@onready var tablebase_adjudication_check := get_node("CenterContainer/VBoxContainer/TablebaseAdjudicationCheck")
+@onready var time_control_option := get_node("CenterContainer/VBoxContainer/TimeControlOption")
+@onready var hint_limit_option := get_node("CenterContainer/VBoxContainer/HintLimitOption")
+@onready var eval_bar_check := get_node("CenterContainer/VBoxContainer/EvalBarCheck")
+
+
+func _ready():
+	var settings = Globals.settings
+	theme_option.select(max(THEMES.find(settings.theme), 0))
+	volume_slider.set_value_no_signal(settings.sound_volume)
+	mute_check.set_pressed_no_signal(settings.muted)
+	animation_slider.set_value_no_signal(settings.animation_speed)
+	legal_moves_check.set_pressed_no_signal(settings.show_legal_moves)
+	auto_queen_check.set_pressed_no_signal(settings.auto_queen)
+	eval_bar_check.set_pressed_no_signal(settings.show_eval_bar)
+	bot_level_spin.set_value_no_signal(settings.bot_level)
+	blunder_chance_slider.set_value_no_signal(settings.blunder_chance)
+	uci_engine_path_edit.set_text(settings.uci_engine_path)
+	book_path_edit.set_text(settings.book_path)
+	book_depth_spin.set_value_no_signal(settings.book_max_plies)
+	book_variety_slider.set_value_no_signal(settings.book_variety)
+	syzygy_path_edit.set_text(settings.syzygy_path)
+	tablebase_adjudication_check.set_pressed_no_signal(settings.tablebase_adjudication)
+	time_control_option.select(max(TIME_CONTROLS.find(settings.time_control), 0))
+	var hint_limit_index = HINT_LIMITS.find(settings.hint_limit)
+	hint_limit_option.select(hint_limit_index if hint_limit_index >= 0 else HINT_LIMITS.size() - 1)
+
+
+func _settings_changed():
+	Globals.settings.save_settings()
+	Globals.settings_changed.emit()
+
+
+func _on_theme_option_item_selected(index: int) -> void:
+	Globals.settings.theme = THEMES[index]
+	_settings_changed()
+
+
+func _on_volume_slider_value_changed(value: float) -> void:
+	Globals.settings.sound_volume = value
+	_settings_changed()
+
+
+func _on_mute_check_toggled(toggled_on: bool) -> void:
+	Globals.settings.muted = toggled_on
+	_settings_changed()
+
+
+func _on_animation_slider_value_changed(value: float) -> void:
+	Globals.settings.animation_speed = value
+	_settings_changed()
+
+
+func _on_legal_moves_check_toggled(toggled_on: bool) -> void:
+	Globals.settings.show_legal_moves = toggled_on
+	_settings_changed()
+
+
+func _on_auto_queen_check_toggled(toggled_on: bool) -> void:
+	Globals.settings.auto_queen = toggled_on
+	_settings_changed()
+
+
+func _on_eval_bar_check_toggled(toggled_on: bool) -> void:
+	Globals.settings.show_eval_bar = toggled_on
+	_settings_changed()
+
+
+func _on_bot_level_spin_value_changed(value: float) -> void:
+	Globals.settings.bot_level = int(value)
+	_settings_changed()
+
+
+func _on_blunder_chance_slider_value_changed(value: float) -> void:
+	Globals.settings.blunder_chance = value
+	_settings_changed()
+
+
+func _on_uci_engine_path_edit_text_submitted(new_text: String) -> void:
+	Globals.settings.uci_engine_path = new_text.strip_edges()
+	_settings_changed()
+
+
+func _on_uci_engine_path_edit_focus_exited() -> void:
+	_on_uci_engine_path_edit_text_submitted(uci_engine_path_edit.get_text())
+
+
+func _on_book_path_edit_text_submitted(new_text: String) -> void:
+	Globals.settings.book_path = new_text.strip_edges()
+	_settings_changed()
+
+
+func _on_book_path_edit_focus_exited() -> void:
+	_on_book_path_edit_text_submitted(book_path_edit.get_text())
+
+
+func _on_book_depth_spin_value_changed(value: float) -> void:
+	Globals.settings.book_max_plies = int(value)
+	_settings_changed()
+
+
+func _on_book_variety_slider_value_changed(value: float) -> void:
+	Globals.settings.book_variety = value
+	_settings_changed()
+
+
+func _on_syzygy_path_edit_text_submitted(new_text: String) -> void:
+	Globals.settings.syzygy_path = new_text.strip_edges()
+	_settings_changed()
+
+
+func _on_syzygy_path_edit_focus_exited() -> void:
+	_on_syzygy_path_edit_text_submitted(syzygy_path_edit.get_text())
+
+
+func _on_tablebase_adjudication_check_toggled(toggled_on: bool) -> void:
+	Globals.settings.tablebase_adjudication = toggled_on
+	_settings_changed()
+
+
+func _on_time_control_option_item_selected(index: int) -> void:
+	Globals.settings.time_control = TIME_CONTROLS[index]
+	_settings_changed()
+
+
+func _on_hint_limit_option_item_selected(index: int) -> void:
+	Globals.settings.hint_limit = HINT_LIMITS[index]
+	_settings_changed()
+
 
 func _on_back_button_pressed() -> void:
+	if overlay:
+		queue_free()
+		return
 	get_tree().change_scene_to_file("res://menu/menu.tscn")
@@ -0,0 +1,90 @@
+use godot::classes::DisplayServer;
+use godot::prelude::*;
+
+use crate::chess_pieces::{GodotPieceColor, GodotPieceKind};
+use crate::consts::TTS_LANGUAGE;
+use crate::square::GodotSquare;
+
+pub struct Announcer {
+    enabled: bool,
+    voice: Option<GString>,
+}
+
+impl Announcer {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            voice: None,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if enabled && self.voice.is_none() {
+            self.voice = DisplayServer::singleton().tts_get_voices_for_language(TTS_LANGUAGE).get(0);
+            if self.voice.is_none() {
+                godot_warn!("No text-to-speech voice available for language '{}'", TTS_LANGUAGE);
+            }
+        }
+    }
+
+    /// Interrupts whatever is being read out, so fast keyboard navigation doesn't queue up stale announcements
+    pub fn speak(&self, text: &str) {
+        if !self.enabled {
+            return;
+        }
+        let Some(voice) = &self.voice else {
+            return;
+        };
+
+        let mut display_server = DisplayServer::singleton();
+        display_server.tts_stop();
+        display_server.tts_speak(text, voice);
+    }
+
+    pub fn describe_piece(color: GodotPieceColor, kind: GodotPieceKind) -> String {
+        format!("{} {}", color.name(), kind.name())
+    }
+
+    pub fn describe_square(square: &GodotSquare, piece: Option<(GodotPieceColor, GodotPieceKind)>) -> String {
+        match piece {
+            Some((color, kind)) => format!("{}, {}", square, Self::describe_piece(color, kind)),
+            None => format!("{}, empty", square),
+        }
+    }
+
+    pub fn describe_move(
+        color: GodotPieceColor,
+        kind: GodotPieceKind,
+        from: &GodotSquare,
+        to: &GodotSquare,
+        captured: Option<GodotPieceKind>,
+        is_check: bool,
+    ) -> String {
+        let mut output = format!("{} from {} to {}", Self::describe_piece(color, kind), from, to);
+        if let Some(captured_kind) = captured {
+            output.push_str(&format!(", captures {}", captured_kind.name()));
+        }
+        if is_check {
+            output.push_str(", check");
+        }
+        output
+    }
+
+    pub fn describe_position(pieces: &[(GodotSquare, GodotPieceColor, GodotPieceKind)], turn: GodotPieceColor) -> String {
+        let mut output = format!("{} to move.", turn.name());
+        for color in [GodotPieceColor::White, GodotPieceColor::Black] {
+            let pieces_of_color: Vec<String> = pieces
+                .iter()
+                .filter(|(_, piece_color, _)| *piece_color == color)
+                .map(|(square, _, kind)| format!("{} {}", kind.name(), square))
+                .collect();
+            output.push_str(&format!(" {}: {}.", color.name(), pieces_of_color.join(", ")));
+        }
+        output
+    }
+}
@@ -0,0 +1,331 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use godot::classes::{ConfigFile, DirAccess};
+use godot::global::Error;
+use godot::prelude::*;
+
+use crate::chess_pieces::GodotPieceColor;
+use crate::consts::ANALYSIS_CACHE_FOLDER_PATH;
+use crate::engine::{ChessEngine, Evaluation};
+use crate::fen;
+use crate::moves::GodotMove;
+use crate::tablebase::Tablebases;
+
+/// Evaluations are capped so a missed mate doesn't outweigh every other mistake of the game
+const EVAL_CAP: i32 = 1_000;
+/// Largest loss in centipawns for each class, anything worse is a blunder
+const BEST_MAX_LOSS: i32 = 10;
+const GOOD_MAX_LOSS: i32 = 50;
+const INACCURACY_MAX_LOSS: i32 = 100;
+const MISTAKE_MAX_LOSS: i32 = 250;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MoveClassification {
+    Best,
+    Good,
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl MoveClassification {
+    const ALL: [Self; 5] = [Self::Best, Self::Good, Self::Inaccuracy, Self::Mistake, Self::Blunder];
+
+    fn from_loss(loss: i32) -> Self {
+        match loss {
+            ..=BEST_MAX_LOSS => Self::Best,
+            ..=GOOD_MAX_LOSS => Self::Good,
+            ..=INACCURACY_MAX_LOSS => Self::Inaccuracy,
+            ..=MISTAKE_MAX_LOSS => Self::Mistake,
+            _ => Self::Blunder,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Best => "best",
+            Self::Good => "good",
+            Self::Inaccuracy => "inaccuracy",
+            Self::Mistake => "mistake",
+            Self::Blunder => "blunder",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|classification| classification.name() == name)
+    }
+}
+
+pub struct PlyAnalysis {
+    pub san: String,
+    pub classification: MoveClassification,
+    /// The bot's choice in SAN, when it was better than the move played
+    pub better_move: Option<String>,
+    /// Centipawns from White's point of view after the move
+    pub white_eval: i32,
+    /// `TablebaseWin` or `TablebaseDraw` from White's point of view when the position after the move is in the
+    /// tablebases
+    pub white_tablebase: Option<Evaluation>,
+}
+
+pub struct GameAnalysis {
+    pub depth: u8,
+    /// Centipawns from White's point of view before the first move
+    pub start_eval: i32,
+    pub start_tablebase: Option<Evaluation>,
+    pub plies: Vec<PlyAnalysis>,
+}
+
+impl GameAnalysis {
+    /// Searches every position of the game, from `start` through each of `moves`. Positions in the tablebases are
+    /// looked up instead, so throwing away a tablebase win counts as a blunder whatever the search thinks.
+    pub fn analyse(
+        mut start: ChessEngine,
+        start_fen: Option<&str>,
+        moves: &[GodotMove],
+        depth: u8,
+        tablebases: Option<&Tablebases>,
+        progress: &AtomicUsize,
+    ) -> Self {
+        let start_turn = start.get_turn();
+        // Scores and tablebase results are from the point of view of the side to move in each position
+        let mut scores = Vec::with_capacity(moves.len() + 1);
+        let mut tablebase_results = Vec::with_capacity(moves.len() + 1);
+        let mut best_moves = Vec::with_capacity(moves.len() + 1);
+        let mut sans = Vec::with_capacity(moves.len());
+        for index in 0..=moves.len() {
+            let probe = tablebases.and_then(|tablebases| tablebases.probe(&fen::get_fen(start_fen, &start)?));
+            let tablebase_move = probe
+                .as_ref()
+                .and_then(|probe| start.get_legal_move_from_uci(probe.best_move.as_deref()?));
+            match (probe, tablebase_move) {
+                (Some(probe), Some(tablebase_move)) => {
+                    scores.push(probe.evaluation.get_capped_centipawns(EVAL_CAP));
+                    tablebase_results.push(Some(probe.evaluation));
+                    best_moves.push(Some(tablebase_move));
+                }
+                _ => {
+                    match start.search(depth) {
+                        Some(result) => {
+                            scores.push(Evaluation::from_score(result.score).get_capped_centipawns(EVAL_CAP));
+                            best_moves.push(Some(result.best_move));
+                        }
+                        None => {
+                            scores.push(if start.is_in_check() { -EVAL_CAP } else { 0 });
+                            best_moves.push(None);
+                        }
+                    }
+                    tablebase_results.push(None);
+                }
+            }
+            if let Some(played_move) = moves.get(index) {
+                let best_san = best_moves[index]
+                    .filter(|best_move| best_move != played_move)
+                    .map(|best_move| start.get_san(&best_move));
+                sans.push((start.get_san(played_move), best_san, start.get_turn()));
+                start.play_move(played_move);
+            }
+            progress.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let plies = sans
+            .into_iter()
+            .enumerate()
+            .map(|(index, (san, best_san, mover))| {
+                let played_score = -scores[index + 1];
+                let loss = if best_san.is_some() {
+                    (scores[index] - played_score).max(0)
+                } else {
+                    0
+                };
+                let classification = MoveClassification::from_loss(loss);
+                PlyAnalysis {
+                    san,
+                    classification,
+                    better_move: best_san.filter(|_| classification != MoveClassification::Best),
+                    white_eval: Self::to_white_view(mover, played_score),
+                    white_tablebase: tablebase_results[index + 1]
+                        .map(|evaluation| Self::to_white_evaluation(mover.opponent_turn(), evaluation)),
+                }
+            })
+            .collect();
+
+        Self {
+            depth,
+            start_eval: Self::to_white_view(start_turn, scores[0]),
+            start_tablebase: tablebase_results[0].map(|evaluation| Self::to_white_evaluation(start_turn, evaluation)),
+            plies,
+        }
+    }
+
+    fn to_white_view(color: GodotPieceColor, score: i32) -> i32 {
+        match color {
+            GodotPieceColor::White => score,
+            GodotPieceColor::Black => -score,
+        }
+    }
+
+    fn to_white_evaluation(color: GodotPieceColor, evaluation: Evaluation) -> Evaluation {
+        match color {
+            GodotPieceColor::White => evaluation,
+            GodotPieceColor::Black => evaluation.flipped(),
+        }
+    }
+
+    /// White's evaluation after `ply` moves, the tablebase result when there is one
+    pub fn get_white_evaluation(&self, ply: usize) -> Option<Evaluation> {
+        let (white_eval, white_tablebase) = match ply {
+            0 => (self.start_eval, self.start_tablebase),
+            _ => {
+                let analyzed_ply = self.plies.get(ply - 1)?;
+                (analyzed_ply.white_eval, analyzed_ply.white_tablebase)
+            }
+        };
+        Some(white_tablebase.unwrap_or(Evaluation::Centipawns(white_eval)))
+    }
+
+    /// Tablebase results are cached as the moves to win from White's point of view, negative when losing and 0 for a
+    /// draw
+    fn tablebase_to_variant(white_tablebase: Evaluation) -> Variant {
+        match white_tablebase {
+            Evaluation::TablebaseWin(moves) => moves.to_variant(),
+            _ => 0.to_variant(),
+        }
+    }
+
+    fn tablebase_from_variant(value: Variant) -> Option<Evaluation> {
+        match value.try_to::<i32>().ok()? {
+            0 => Some(Evaluation::TablebaseDraw),
+            moves => Some(Evaluation::TablebaseWin(moves)),
+        }
+    }
+
+    /// Reads an analysis saved by `save`, if there is one for `key` at `depth`
+    pub fn load(key: &str, depth: u8) -> Option<Self> {
+        let mut config = ConfigFile::new_gd();
+        if config.load(&Self::get_cache_path(key)) != Error::OK {
+            return None;
+        }
+        let read = |section: &str, key: &str| config.get_value(section, key);
+        if read("game", "depth").try_to::<i32>().ok()? != depth as i32 {
+            return None;
+        }
+
+        let ply_count = read("game", "plies").try_to::<i32>().ok()?;
+        let mut plies = Vec::with_capacity(ply_count.max(0) as usize);
+        for index in 0..ply_count {
+            let section = format!("ply_{}", index);
+            let better_move = read(&section, "better_move").try_to::<GString>().ok()?.to_string();
+            plies.push(PlyAnalysis {
+                san: read(&section, "san").try_to::<GString>().ok()?.to_string(),
+                classification: MoveClassification::from_name(
+                    &read(&section, "classification").try_to::<GString>().ok()?.to_string(),
+                )?,
+                better_move: (!better_move.is_empty()).then_some(better_move),
+                white_eval: read(&section, "white_eval").try_to::<i32>().ok()?,
+                white_tablebase: Self::tablebase_from_variant(read(&section, "tablebase")),
+            });
+        }
+        Some(Self {
+            depth,
+            start_eval: read("game", "start_eval").try_to::<i32>().ok()?,
+            start_tablebase: Self::tablebase_from_variant(read("game", "start_tablebase")),
+            plies,
+        })
+    }
+
+    pub fn save(&self, key: &str) -> bool {
+        DirAccess::make_dir_recursive_absolute(ANALYSIS_CACHE_FOLDER_PATH);
+        let mut config = ConfigFile::new_gd();
+        config.set_value("game", "depth", &(self.depth as i32).to_variant());
+        config.set_value("game", "start_eval", &self.start_eval.to_variant());
+        if let Some(start_tablebase) = self.start_tablebase {
+            config.set_value("game", "start_tablebase", &Self::tablebase_to_variant(start_tablebase));
+        }
+        config.set_value("game", "plies", &(self.plies.len() as i32).to_variant());
+        for (index, ply) in self.plies.iter().enumerate() {
+            let section = format!("ply_{}", index);
+            let better_move = ply.better_move.clone().unwrap_or_default();
+            config.set_value(&section, "san", &ply.san.to_variant());
+            config.set_value(&section, "classification", &ply.classification.name().to_variant());
+            config.set_value(&section, "better_move", &better_move.to_variant());
+            config.set_value(&section, "white_eval", &ply.white_eval.to_variant());
+            if let Some(white_tablebase) = ply.white_tablebase {
+                config.set_value(&section, "tablebase", &Self::tablebase_to_variant(white_tablebase));
+            }
+        }
+
+        let path = Self::get_cache_path(key);
+        let result = config.save(&path);
+        if result != Error::OK {
+            godot_error!("Failed to save the game analysis to {}: {:?}", path, result);
+        }
+        result == Error::OK
+    }
+
+    /// Identifies a game by where it started and the moves played, stable across runs and builds
+    pub fn get_cache_key(start_fen: Option<&str>, moves: &[GodotMove]) -> String {
+        // FNV-1a
+        let mut hash: u64 = 0xcbf29ce484222325;
+        let moves: Vec<String> = moves.iter().map(|played_move| played_move.to_uci()).collect();
+        for byte in format!("{}|{}", start_fen.unwrap_or("startpos"), moves.join(" ")).bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        format!("{:016x}", hash)
+    }
+
+    fn get_cache_path(key: &str) -> String {
+        format!("{}{}.cfg", ANALYSIS_CACHE_FOLDER_PATH, key)
+    }
+}
+
+/// A game analysis running on its own thread
+pub struct AnalysisJob {
+    progress: Arc<AtomicUsize>,
+    receiver: Receiver<GameAnalysis>,
+    total: usize,
+}
+
+impl AnalysisJob {
+    pub fn start(
+        start: ChessEngine,
+        start_fen: Option<String>,
+        moves: Vec<GodotMove>,
+        depth: u8,
+        tablebases: Option<Arc<Tablebases>>,
+    ) -> Self {
+        let progress = Arc::new(AtomicUsize::new(0));
+        let total = moves.len() + 1;
+        let (sender, receiver) = mpsc::channel();
+        let thread_progress = progress.clone();
+        thread::spawn(move || {
+            let analysis = GameAnalysis::analyse(
+                start,
+                start_fen.as_deref(),
+                &moves,
+                depth,
+                tablebases.as_deref(),
+                &thread_progress,
+            );
+            let _ = sender.send(analysis);
+        });
+        Self {
+            progress,
+            receiver,
+            total,
+        }
+    }
+
+    /// Positions searched so far and in total
+    pub fn get_progress(&self) -> (usize, usize) {
+        (self.progress.load(Ordering::Relaxed), self.total)
+    }
+
+    pub fn try_take(&self) -> Option<GameAnalysis> {
+        self.receiver.try_recv().ok()
+    }
+}
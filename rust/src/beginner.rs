@@ -0,0 +1,227 @@
+use crate::chess_pieces::GodotPieceKind;
+use crate::consts::MIN_BOT_LEVEL;
+use crate::engine::{BackgroundSearch, ChessEngine};
+use crate::moves::GodotMove;
+use crate::opponent::{MoveRequest, Opponent, OpponentEvent, Rng};
+
+/// How a beginner bot picks its moves
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Personality {
+    /// Any legal move, all equally likely
+    Random,
+    /// Takes the most valuable piece it can, otherwise moves at random
+    Greedy,
+    /// Like `Greedy`, but never leaves its queen where it can be taken
+    Cautious,
+    /// The bot's move at the lowest level, except for a random other move with this chance
+    Blunderer(f64),
+}
+
+impl Personality {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Random => "Random bot",
+            Self::Greedy => "Greedy bot",
+            Self::Cautious => "Cautious bot",
+            Self::Blunderer(_) => "Blundering bot",
+        }
+    }
+}
+
+/// Opponents far weaker than the bot at any level, for players still learning the moves
+pub struct BeginnerBot {
+    /// The blunderer's legal moves while it searches
+    legal_moves: Vec<GodotMove>,
+    personality: Personality,
+    /// Picked without searching, handed out by the next `poll`
+    prepared_move: Option<String>,
+    rng: Rng,
+    search: Option<BackgroundSearch>,
+}
+
+impl BeginnerBot {
+    pub fn new(personality: Personality) -> Self {
+        Self {
+            legal_moves: Vec::new(),
+            personality,
+            prepared_move: None,
+            rng: Rng::new(),
+            search: None,
+        }
+    }
+
+    /// The capture of the most valuable piece among `moves`, a random one of them if none captures
+    fn pick_greedy(&mut self, engine: &ChessEngine, moves: &[GodotMove]) -> Option<GodotMove> {
+        let get_gain = |candidate: &GodotMove| engine.get_captured_kind(candidate).map_or(0, get_piece_value);
+        let best_gain = moves.iter().map(get_gain).max()?;
+        let best_moves: Vec<GodotMove> = moves
+            .iter()
+            .filter(|candidate| get_gain(candidate) == best_gain)
+            .copied()
+            .collect();
+        self.rng.pick(&best_moves)
+    }
+
+    fn leaves_queen_en_prise(engine: &ChessEngine, candidate: &GodotMove) -> bool {
+        let mut after = engine.copy_position();
+        after.play_move(candidate);
+        let replies: Vec<GodotMove> = after.get_legal_moves().into_values().flatten().collect();
+        replies
+            .iter()
+            .any(|reply| after.get_captured_kind(reply) == Some(GodotPieceKind::Queen))
+    }
+}
+
+impl Opponent for BeginnerBot {
+    fn get_name(&self) -> String {
+        self.personality.name().to_string()
+    }
+
+    fn request_move(&mut self, request: &MoveRequest) -> Result<(), String> {
+        let engine = request.engine;
+        let legal_moves: Vec<GodotMove> = engine.copy_position().get_legal_moves().into_values().flatten().collect();
+        let picked = match self.personality {
+            Personality::Random => self.rng.pick(&legal_moves),
+            Personality::Greedy => self.pick_greedy(engine, &legal_moves),
+            Personality::Cautious => {
+                let safe_moves: Vec<GodotMove> = legal_moves
+                    .iter()
+                    .filter(|candidate| !Self::leaves_queen_en_prise(engine, candidate))
+                    .copied()
+                    .collect();
+                // Sometimes every move loses the queen
+                let candidates = if safe_moves.is_empty() { &legal_moves } else { &safe_moves };
+                self.pick_greedy(engine, candidates)
+            }
+            Personality::Blunderer(_) => {
+                self.search = Some(engine.search_in_background(MIN_BOT_LEVEL as u8));
+                self.legal_moves = legal_moves;
+                return Ok(());
+            }
+        };
+        let picked = picked.ok_or("the bot found no move in a position that isn't over")?;
+        self.prepared_move = Some(picked.to_uci());
+        Ok(())
+    }
+
+    fn poll(&mut self) -> Result<Vec<OpponentEvent>, String> {
+        if let Some(prepared_move) = self.prepared_move.take() {
+            return Ok(vec![OpponentEvent::Move(prepared_move)]);
+        }
+        let Some(result) = self.search.as_ref().and_then(|search| search.try_take()) else {
+            return Ok(Vec::new());
+        };
+        self.search = None;
+        let Some(result) = result else {
+            return Err("the bot found no move in a position that isn't over".to_string());
+        };
+        let blunder_chance = match self.personality {
+            Personality::Blunderer(chance) => chance,
+            _ => 0.,
+        };
+        let other_moves: Vec<GodotMove> = std::mem::take(&mut self.legal_moves)
+            .into_iter()
+            .filter(|legal_move| *legal_move != result.best_move)
+            .collect();
+        let blunders = self.rng.next_unit() < blunder_chance;
+        let played_move = match self.rng.pick(&other_moves) {
+            Some(blunder) if blunders => blunder,
+            _ => result.best_move,
+        };
+        Ok(vec![OpponentEvent::Move(played_move.to_uci())])
+    }
+
+    fn cancel(&mut self) {
+        self.legal_moves.clear();
+        self.prepared_move = None;
+        self.search = None;
+    }
+}
+
+/// Material in pawns, the king is never captured
+fn get_piece_value(kind: GodotPieceKind) -> u8 {
+    match kind {
+        GodotPieceKind::Pawn => 1,
+        GodotPieceKind::Knight | GodotPieceKind::Bishop => 3,
+        GodotPieceKind::Rook => 5,
+        GodotPieceKind::Queen => 9,
+        GodotPieceKind::King | GodotPieceKind::Na => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use super::{BeginnerBot, Personality};
+    use crate::consts::SOURCE_MOVE_FOLDER_PATH;
+    use crate::engine::ChessEngine;
+    use crate::opponent::{MoveRequest, Opponent, OpponentEvent, Rng};
+    use crate::tables;
+
+    /// White's pawn can take a queen or a rook
+    const QUEEN_OR_ROOK_FEN: &str = "4k3/8/8/3q1r2/4P3/8/8/4K3 w - - 0 1";
+    /// White's queen can take a pawn, which the other pawn takes back
+    const DEFENDED_PAWN_FEN: &str = "4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1";
+
+    fn get_engine(fen: &str) -> ChessEngine {
+        tables::load_from_folder(SOURCE_MOVE_FOLDER_PATH).unwrap();
+        ChessEngine::from_fen(fen).unwrap()
+    }
+
+    fn play(personality: Personality, seed: u64, engine: &ChessEngine) -> String {
+        let mut bot = BeginnerBot::new(personality);
+        bot.rng = Rng::with_seed(seed);
+        let request = MoveRequest {
+            engine,
+            start_fen: None,
+            moves: &[],
+            clock: None,
+        };
+        bot.request_move(&request).unwrap();
+        let give_up = Instant::now() + Duration::from_secs(10);
+        while Instant::now() < give_up {
+            if let Some(OpponentEvent::Move(played_move)) = bot.poll().unwrap().pop() {
+                return played_move;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        panic!("the bot didn't move");
+    }
+
+    #[test]
+    fn greedy_takes_the_most_valuable_piece() {
+        let engine = get_engine(QUEEN_OR_ROOK_FEN);
+        for seed in 0..20 {
+            assert_eq!(play(Personality::Greedy, seed, &engine), "e4d5");
+        }
+    }
+
+    #[test]
+    fn cautious_never_leaves_its_queen_en_prise() {
+        let engine = get_engine(DEFENDED_PAWN_FEN);
+        for seed in 0..20 {
+            let played_move = play(Personality::Cautious, seed, &engine);
+            let candidate = engine.copy_position().get_legal_move_from_uci(&played_move).unwrap();
+            assert!(!BeginnerBot::leaves_queen_en_prise(&engine, &candidate), "{}", played_move);
+        }
+        // The greedy bot takes the pawn and loses its queen
+        assert_eq!(play(Personality::Greedy, 0, &engine), "d1d5");
+    }
+
+    #[test]
+    fn blunderer_plays_the_searched_move_unless_it_blunders() {
+        let engine = get_engine(QUEEN_OR_ROOK_FEN);
+        for seed in 0..5 {
+            assert_eq!(play(Personality::Blunderer(0.), seed, &engine), "e4d5");
+            let blunder = play(Personality::Blunderer(1.), seed, &engine);
+            assert_ne!(blunder, "e4d5");
+            assert!(
+                engine.copy_position().get_legal_move_from_uci(&blunder).is_some(),
+                "{}",
+                blunder
+            );
+        }
+    }
+}
@@ -0,0 +1,258 @@
+//! A game against the bot in the terminal, for quick testing and SSH sessions. Build it without Godot with
+//! `cargo run --bin chess-cli --no-default-features -- [--black] [--level N] [--fen FEN] [--tables FOLDER]`.
+
+use std::env;
+use std::io::{self, BufRead, Write};
+use std::process;
+
+use chess::chess_pieces::{GodotPieceColor, GodotPieceKind};
+use chess::consts::{DEFAULT_BOT_LEVEL, MAX_BOT_LEVEL, MIN_BOT_LEVEL, SOURCE_MOVE_FOLDER_PATH};
+use chess::engine::ChessEngine;
+use chess::fen::{self, START_POSITION_FEN};
+use chess::moves::GodotMove;
+use chess::pgn::GameRecord;
+use chess::square::GodotSquare;
+use chess::tables;
+
+const HELP: &str = "Moves in SAN (Nf3, exd5, O-O, e8=Q) or UCI (g1f3). Commands: undo, fen, pgn, flip, help, quit";
+
+struct Options {
+    player_color: GodotPieceColor,
+    level: u8,
+    fen: String,
+    tables_folder: String,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Self {
+            player_color: GodotPieceColor::White,
+            level: DEFAULT_BOT_LEVEL as u8,
+            fen: START_POSITION_FEN.to_string(),
+            tables_folder: SOURCE_MOVE_FOLDER_PATH.to_string(),
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--black" => options.player_color = GodotPieceColor::Black,
+                "--level" => {
+                    let level = args.next().and_then(|level| level.parse::<i32>().ok());
+                    options.level = match level {
+                        Some(level) if (MIN_BOT_LEVEL..=MAX_BOT_LEVEL).contains(&level) => level as u8,
+                        _ => return Err(format!("--level expects {} to {}", MIN_BOT_LEVEL, MAX_BOT_LEVEL)),
+                    };
+                }
+                "--fen" => options.fen = args.next().ok_or("--fen expects a FEN in quotes")?,
+                "--tables" => options.tables_folder = args.next().ok_or("--tables expects a folder")?,
+                other => return Err(format!("Unknown argument '{}'", other)),
+            }
+        }
+        if !options.tables_folder.ends_with('/') {
+            options.tables_folder.push('/');
+        }
+        Ok(options)
+    }
+}
+
+fn main() {
+    let options = Options::parse(env::args().skip(1)).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(2);
+    });
+    if let Err(error) = tables::load_from_folder(&options.tables_folder) {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+    let mut game = TerminalGame::new(options).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(2);
+    });
+    game.run();
+}
+
+struct TerminalGame {
+    engine: ChessEngine,
+    level: u8,
+    orientation: GodotPieceColor,
+    player_color: GodotPieceColor,
+    record: GameRecord,
+    /// `None` while the game is going on
+    result: Option<String>,
+}
+
+impl TerminalGame {
+    fn new(options: Options) -> Result<Self, String> {
+        let engine = ChessEngine::from_fen(&options.fen).map_err(|error| format!("Invalid FEN: {}", error))?;
+        let is_start_position = options.fen == START_POSITION_FEN;
+        Ok(Self {
+            engine,
+            level: options.level,
+            orientation: options.player_color,
+            player_color: options.player_color,
+            record: GameRecord::new((!is_start_position).then_some(options.fen.as_str())),
+            result: None,
+        })
+    }
+
+    fn run(&mut self) {
+        println!("{}", HELP);
+        self.check_game_over();
+        let mut lines = io::stdin().lock().lines();
+        loop {
+            if self.result.is_none() && self.engine.get_turn() != self.player_color {
+                self.play_bot_move();
+                continue;
+            }
+            self.print_board();
+            print!("> ");
+            let _ = io::stdout().flush();
+            let Some(Ok(line)) = lines.next() else {
+                return;
+            };
+            match line.trim() {
+                "" => {}
+                "quit" | "exit" => return,
+                "help" => println!("{}", HELP),
+                "flip" => self.orientation = self.orientation.opponent_turn(),
+                "fen" => println!(
+                    "{}",
+                    fen::get_fen(self.record.get_start_fen(), &self.engine).unwrap_or_default()
+                ),
+                "pgn" => print!("{}", self.get_pgn()),
+                "undo" => self.undo(),
+                input => self.play_player_move(input),
+            }
+        }
+    }
+
+    fn play_player_move(&mut self, input: &str) {
+        if let Some(result) = &self.result {
+            println!("The game is over ({}), try undo", result);
+            return;
+        }
+        match self.find_move(input) {
+            Some(legal_move) => self.play_move(&legal_move),
+            None => println!("'{}' isn't a legal move here. {}", input, HELP),
+        }
+    }
+
+    /// Accepts UCI, or SAN with or without check marks, `=` before the promotion piece, and zeros for castling
+    fn find_move(&mut self, input: &str) -> Option<GodotMove> {
+        if let Some(legal_move) = self.engine.get_legal_move_from_uci(&input.to_ascii_lowercase()) {
+            return Some(legal_move);
+        }
+        let normalize = |san: &str| san.replace(['+', '#', '!', '?', '='], "").replace('0', "O");
+        let input = normalize(input);
+        let legal_moves: Vec<GodotMove> = self.engine.get_legal_moves().into_values().flatten().collect();
+        legal_moves
+            .into_iter()
+            .find(|legal_move| normalize(&self.engine.get_san(legal_move)) == input)
+    }
+
+    fn play_bot_move(&mut self) {
+        println!("Bot is thinking...");
+        let Some(result) = self.engine.search(self.level) else {
+            // Only reached when the game is over, which `check_game_over` has already caught
+            return;
+        };
+        let san = self.engine.get_san(&result.best_move);
+        println!("Bot plays {} ({})", san, result.format_score());
+        self.play_move(&result.best_move);
+    }
+
+    fn play_move(&mut self, legal_move: &GodotMove) {
+        let san = self.engine.get_san(legal_move);
+        self.engine.play_move(legal_move);
+        self.record.push_move(&san);
+        self.check_game_over();
+    }
+
+    fn check_game_over(&mut self) {
+        if !self.engine.get_legal_moves().is_empty() {
+            return;
+        }
+        let turn = self.engine.get_turn();
+        let (result, reason) = if self.engine.is_in_check() {
+            (turn.opponent_turn().get_win_result(), "checkmate")
+        } else {
+            ("1/2-1/2", "stalemate")
+        };
+        self.record.set_result(result);
+        self.result = Some(result.to_string());
+        println!("Game over by {}, {}", reason, result);
+    }
+
+    /// Takes back the player's last move, together with the bot's reply to it
+    fn undo(&mut self) {
+        let mut taken_back = 0;
+        while self.engine.get_ply() > 0 && (taken_back == 0 || self.engine.get_turn() != self.player_color) {
+            self.engine.undo_move();
+            self.record.pop_move();
+            taken_back += 1;
+        }
+        if taken_back == 0 {
+            println!("No moves to take back");
+            return;
+        }
+        self.result = None;
+    }
+
+    fn print_board(&self) {
+        let mut output = String::new();
+        for row in 0..8 {
+            let rank = GodotSquare::from_u8(0, row, &self.orientation).get_rank();
+            output.push_str(&(rank + 1).to_string());
+            for column in 0..8 {
+                let square = GodotSquare::from_u8(column, row, &self.orientation);
+                let symbol = match self.engine.get_piece_on_square(&square) {
+                    Some((color, kind)) => get_piece_symbol(color, kind),
+                    None => '·',
+                };
+                output.push(' ');
+                output.push(symbol);
+            }
+            output.push('\n');
+        }
+        output.push(' ');
+        for column in 0..8 {
+            let file = GodotSquare::from_u8(column, 0, &self.orientation).get_file();
+            output.push(' ');
+            output.push((b'a' + file) as char);
+        }
+        println!("\n{}\n{} to move", output, self.engine.get_turn().name());
+    }
+
+    fn get_pgn(&self) -> String {
+        let bot_name = format!("Bot level {}", self.level);
+        let (white, black) = match self.player_color {
+            GodotPieceColor::White => ("Player".to_string(), bot_name),
+            GodotPieceColor::Black => (bot_name, "Player".to_string()),
+        };
+        let tags = [
+            ("Event", "Terminal game".to_string()),
+            ("Site", "?".to_string()),
+            ("Date", "????.??.??".to_string()),
+            ("Round", "-".to_string()),
+            ("White", white),
+            ("Black", black),
+        ];
+        self.record.to_pgn(&tags)
+    }
+}
+
+fn get_piece_symbol(color: GodotPieceColor, kind: GodotPieceKind) -> char {
+    match (color, kind) {
+        (GodotPieceColor::White, GodotPieceKind::King) => '♔',
+        (GodotPieceColor::White, GodotPieceKind::Queen) => '♕',
+        (GodotPieceColor::White, GodotPieceKind::Rook) => '♖',
+        (GodotPieceColor::White, GodotPieceKind::Bishop) => '♗',
+        (GodotPieceColor::White, GodotPieceKind::Knight) => '♘',
+        (GodotPieceColor::White, GodotPieceKind::Pawn) => '♙',
+        (GodotPieceColor::Black, GodotPieceKind::King) => '♚',
+        (GodotPieceColor::Black, GodotPieceKind::Queen) => '♛',
+        (GodotPieceColor::Black, GodotPieceKind::Rook) => '♜',
+        (GodotPieceColor::Black, GodotPieceKind::Bishop) => '♝',
+        (GodotPieceColor::Black, GodotPieceKind::Knight) => '♞',
+        (GodotPieceColor::Black, GodotPieceKind::Pawn) => '♟',
+        (_, GodotPieceKind::Na) => '?',
+    }
+}
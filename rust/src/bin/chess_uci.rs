@@ -0,0 +1,328 @@
+//! The game's bot as a UCI engine, for GUIs like Cute Chess. Takes the folder with the move tables as its only
+//! argument, by default the one in the Godot project.
+
+use std::env;
+use std::io::{self, BufRead};
+use std::process;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chess::chess_pieces::GodotPieceColor;
+use chess::consts::{DEFAULT_BOT_LEVEL, MAX_BOT_LEVEL, MIN_BOT_LEVEL, SOURCE_MOVE_FOLDER_PATH};
+use chess::engine::{ChessEngine, Evaluation, SearchResult};
+use chess::tables;
+
+/// How often a running search is checked while waiting for commands
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+/// Moves the remaining time is shared between when the GUI doesn't say
+const DEFAULT_MOVES_TO_GO: u64 = 30;
+/// Kept back from the clock for the time it takes the GUI to pass the move on
+const MOVE_OVERHEAD_MS: u64 = 50;
+/// Roughly how many times longer each depth takes than the one before
+const DEPTH_TIME_GROWTH: u32 = 4;
+
+fn main() {
+    let mut folder = env::args().nth(1).unwrap_or(SOURCE_MOVE_FOLDER_PATH.to_string());
+    if !folder.ends_with('/') {
+        folder.push('/');
+    }
+    if let Err(error) = tables::load_from_folder(&folder) {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+
+    // Reading stdin blocks, so it gets a thread of its own and searches keep being checked in between commands
+    let (sender, commands) = mpsc::channel();
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    let mut server = UciServer::new().unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(1)
+    });
+    server.run(&commands);
+}
+
+/// Iterative deepening on a thread of its own. Every finished depth is sent back, so the search can be cut short
+/// with the move of the deepest one. Dropping it stops the search after the depth in progress.
+///
+/// The bot's search of a single depth can't be interrupted, so after `stop` or the deadline the thread keeps a core
+/// busy until that depth is done. With a deadline it only starts the depths expected to finish in time, which keeps
+/// the overrun short and lets the answer come as soon as nothing deeper fits.
+struct Search {
+    best: Option<SearchResult>,
+    deadline: Option<Instant>,
+    /// `go infinite` only answers after `stop`
+    infinite: bool,
+    receiver: Receiver<(u8, SearchResult)>,
+    start_time: Instant,
+    stop: Arc<AtomicBool>,
+}
+
+impl Drop for Search {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Search {
+    fn start(mut engine: ChessEngine, max_depth: u8, deadline: Option<Instant>, infinite: bool) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        thread::spawn(move || {
+            let mut last_depth_time = Duration::ZERO;
+            for depth in 1..=max_depth {
+                let expected_end = Instant::now() + last_depth_time * DEPTH_TIME_GROWTH;
+                let too_slow = deadline.is_some_and(|deadline| expected_end > deadline);
+                // The first depth always runs, so there is a move to answer with
+                if depth > 1 && (thread_stop.load(Ordering::Relaxed) || too_slow) {
+                    return;
+                }
+                let depth_start = Instant::now();
+                // No legal moves, which `finish` answers with a null move
+                let Some(result) = engine.search(depth) else {
+                    return;
+                };
+                last_depth_time = depth_start.elapsed();
+                if sender.send((depth, result)).is_err() {
+                    return;
+                }
+            }
+        });
+        Self {
+            best: None,
+            deadline,
+            infinite,
+            receiver,
+            start_time: Instant::now(),
+            stop,
+        }
+    }
+
+    /// Reports the depths finished since the last call. Returns true once every depth is done.
+    fn poll(&mut self) -> bool {
+        loop {
+            match self.receiver.try_recv() {
+                Ok((depth, result)) => self.record(depth, result),
+                Err(TryRecvError::Empty) => return false,
+                Err(TryRecvError::Disconnected) => return true,
+            }
+        }
+    }
+
+    fn is_over(&mut self) -> bool {
+        let finished = self.poll();
+        let out_of_time = self.deadline.is_some_and(|deadline| Instant::now() >= deadline);
+        (finished && !self.infinite) || out_of_time
+    }
+
+    /// Answers with the move of the deepest finished depth, waiting for the first one if needed
+    fn finish(mut self) {
+        self.poll();
+        if self.best.is_none()
+            && let Ok((depth, result)) = self.receiver.recv()
+        {
+            self.record(depth, result);
+        }
+        match &self.best {
+            Some(result) => println!("bestmove {}", result.best_move.to_uci()),
+            None => println!("bestmove 0000"),
+        }
+    }
+
+    fn record(&mut self, depth: u8, result: SearchResult) {
+        let score = match Evaluation::from_score(result.score) {
+            Evaluation::Centipawns(centipawns) => format!("cp {}", centipawns),
+            Evaluation::MateIn(moves) => format!("mate {}", moves),
+            // Search scores are never tablebase results
+            Evaluation::TablebaseWin(_) | Evaluation::TablebaseDraw => format!("cp {}", result.score),
+        };
+        println!(
+            "info depth {} score {} time {} pv {}",
+            depth,
+            score,
+            self.start_time.elapsed().as_millis(),
+            result.best_move.to_uci()
+        );
+        self.best = Some(result);
+    }
+}
+
+struct UciServer {
+    level: u8,
+    position: ChessEngine,
+    search: Option<Search>,
+}
+
+impl UciServer {
+    /// Fails if the engine tables aren't loaded yet
+    fn new() -> Result<Self, String> {
+        Ok(Self {
+            level: DEFAULT_BOT_LEVEL as u8,
+            position: ChessEngine::new()?,
+            search: None,
+        })
+    }
+
+    fn run(&mut self, commands: &Receiver<String>) {
+        loop {
+            let command = if self.search.is_some() {
+                match commands.recv_timeout(POLL_INTERVAL) {
+                    Ok(command) => Some(command),
+                    Err(RecvTimeoutError::Timeout) => None,
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+            } else {
+                match commands.recv() {
+                    Ok(command) => Some(command),
+                    Err(_) => return,
+                }
+            };
+            if let Some(command) = command
+                && !self.handle_command(&command)
+            {
+                return;
+            }
+
+            if self.search.as_mut().is_some_and(|search| search.is_over()) {
+                self.finish_search();
+            }
+        }
+    }
+
+    /// Returns false on `quit`
+    fn handle_command(&mut self, line: &str) -> bool {
+        let mut tokens = line.split_whitespace();
+        let command = tokens.next();
+        let args: Vec<&str> = tokens.collect();
+        match command {
+            Some("uci") => {
+                println!("id name Chess bot {}", env!("CARGO_PKG_VERSION"));
+                println!(
+                    "option name Level type spin default {} min {} max {}",
+                    DEFAULT_BOT_LEVEL, MIN_BOT_LEVEL, MAX_BOT_LEVEL
+                );
+                println!("uciok");
+            }
+            Some("isready") => println!("readyok"),
+            Some("ucinewgame") => self.set_position(&["startpos"]),
+            Some("setoption") => self.set_option(&args),
+            Some("position") => self.set_position(&args),
+            Some("go") => self.go(&args),
+            Some("stop") => self.finish_search(),
+            Some("quit") => return false,
+            // The protocol asks for anything else to be ignored
+            _ => {}
+        }
+        true
+    }
+
+    /// `setoption name Level value 5`
+    fn set_option(&mut self, args: &[&str]) {
+        let value_index = args.iter().position(|arg| *arg == "value").unwrap_or(args.len());
+        let name = args.get(1..value_index).unwrap_or_default().join(" ");
+        let value = args.get(value_index + 1..).unwrap_or_default().join(" ");
+        if !name.eq_ignore_ascii_case("level") {
+            println!("info string Unknown option '{}'", name);
+            return;
+        }
+        match value.parse::<i32>() {
+            Ok(level) if (MIN_BOT_LEVEL..=MAX_BOT_LEVEL).contains(&level) => self.level = level as u8,
+            _ => println!("info string Level must be {} to {}", MIN_BOT_LEVEL, MAX_BOT_LEVEL),
+        }
+    }
+
+    /// `position startpos moves e2e4 e7e5` or `position fen <fen> moves ...`. The position is kept as it was if the
+    /// new one is invalid.
+    fn set_position(&mut self, args: &[&str]) {
+        let (setup, moves) = match args.iter().position(|arg| *arg == "moves") {
+            Some(index) => (&args[..index], &args[index + 1..]),
+            None => (args, &[][..]),
+        };
+        let position = match setup {
+            ["startpos"] => ChessEngine::new(),
+            ["fen", fields @ ..] => ChessEngine::from_fen(&fields.join(" ")).map_err(|error| error.to_string()),
+            _ => Err("expected startpos or fen".to_string()),
+        };
+        let mut position = match position {
+            Ok(position) => position,
+            Err(error) => {
+                println!("info string Invalid position: {}", error);
+                return;
+            }
+        };
+        for uci in moves {
+            let Some(legal_move) = position.get_legal_move_from_uci(uci) else {
+                println!("info string Illegal move {}", uci);
+                return;
+            };
+            position.play_move(&legal_move);
+        }
+        self.position = position;
+    }
+
+    /// Searches up to the level's depth, or the `depth` given, within `movetime` or a share of the clock
+    fn go(&mut self, args: &[&str]) {
+        if self.search.is_some() {
+            return;
+        }
+        let mut max_depth = self.level;
+        let mut move_time = None;
+        let mut remaining = [None, None];
+        let mut increment = [0, 0];
+        let mut moves_to_go = DEFAULT_MOVES_TO_GO;
+        let mut infinite = false;
+
+        let mut tokens = args.iter();
+        while let Some(token) = tokens.next() {
+            match *token {
+                "infinite" => infinite = true,
+                "ponder" => {}
+                name => {
+                    let value = tokens.next().and_then(|value| value.parse::<u64>().ok());
+                    match (name, value) {
+                        ("depth", Some(depth)) => max_depth = depth.clamp(1, u8::MAX as u64) as u8,
+                        ("movetime", Some(milliseconds)) => move_time = Some(milliseconds),
+                        ("wtime", Some(milliseconds)) => remaining[0] = Some(milliseconds),
+                        ("btime", Some(milliseconds)) => remaining[1] = Some(milliseconds),
+                        ("winc", Some(milliseconds)) => increment[0] = milliseconds,
+                        ("binc", Some(milliseconds)) => increment[1] = milliseconds,
+                        ("movestogo", Some(moves)) => moves_to_go = moves.max(1),
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        let side = match self.position.get_turn() {
+            GodotPieceColor::White => 0,
+            GodotPieceColor::Black => 1,
+        };
+        let clock_share = remaining[side].map(|remaining| {
+            let share = remaining / moves_to_go + increment[side] / 2;
+            share.min(remaining.saturating_sub(MOVE_OVERHEAD_MS))
+        });
+        let deadline = move_time
+            .or(clock_share)
+            .filter(|_| !infinite)
+            .map(|milliseconds| Instant::now() + Duration::from_millis(milliseconds));
+        self.search = Some(Search::start(self.position.copy_position(), max_depth, deadline, infinite));
+    }
+
+    fn finish_search(&mut self) {
+        if let Some(search) = self.search.take() {
+            search.finish();
+        }
+    }
+}
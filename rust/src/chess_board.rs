@@ -1,8 +1,10 @@
 use core::f64;
 
 use godot::classes::image::Format;
+use godot::classes::notify::ControlNotification;
 use godot::classes::{
-    ColorRect, IColorRect, IPolygon2D, IReferenceRect, ITextureRect, Image, ImageTexture, Polygon2D, ReferenceRect, TextureRect,
+    ColorRect, IColorRect, IPolygon2D, IReferenceRect, ITextureRect, Image, ImageTexture, Label, Polygon2D, ReferenceRect,
+    TextureRect,
 };
 use godot::prelude::*;
 
@@ -10,9 +12,19 @@ use crate::chess_pieces::{GodotPiece, GodotPieceColor, GodotPieceKind};
 use crate::consts::*;
 use crate::square::GodotSquare;
 
+/// Frees a node allocated up front that never made it into the tree, which would otherwise leak
+pub fn free_if_orphaned<T: Inherits<Node>>(node: Gd<T>) {
+    let node = node.upcast::<Node>();
+    if node.get_parent().is_none() {
+        node.free();
+    }
+}
+
 #[derive(GodotClass)]
 #[class(base=TextureRect)]
 pub struct GodotBoard {
+    dark_color: Color,
+    light_color: Color,
     square_size: f32,
     base: Base<TextureRect>,
 }
@@ -20,24 +32,57 @@ pub struct GodotBoard {
 #[godot_api]
 impl ITextureRect for GodotBoard {
     fn init(base: Base<TextureRect>) -> Self {
-        Self { square_size: 0., base }
+        Self {
+            dark_color: Color::from_html(DARK_SQUARE_COLOR).unwrap(),
+            light_color: Color::from_html(LIGHT_SQUARE_COLOR).unwrap(),
+            square_size: 0.,
+            base,
+        }
     }
 
     fn ready(&mut self) {
         let board_size = self.square_size * 8.;
-        let square_size_int = self.square_size as i32;
         let mut base = self.base_mut();
         base.set_position(Vector2::ZERO);
         base.set_size(Vector2::new(board_size, board_size));
+        drop(base);
+        self.draw_squares();
+    }
+}
+
+impl GodotBoard {
+    pub fn set_square_size(&mut self, size: f32) {
+        self.square_size = size;
+    }
 
-        let mut image = Image::create_empty(board_size as i32, board_size as i32, false, Format::RGB8).unwrap();
+    /// Unknown theme names fall back to the default colours
+    pub fn set_theme(&mut self, theme: &str) {
+        let (_, light_color, dark_color) = BOARD_THEMES
+            .iter()
+            .find(|(name, _, _)| *name == theme)
+            .unwrap_or(&BOARD_THEMES[0]);
+        self.light_color = Color::from_html(light_color).unwrap();
+        self.dark_color = Color::from_html(dark_color).unwrap();
+
+        if self.square_size > 0. {
+            self.draw_squares();
+        }
+    }
+
+    fn draw_squares(&mut self) {
+        let board_size = self.square_size * 8.;
+        let square_size_int = self.square_size as i32;
+
+        let Some(mut image) = Image::create_empty(board_size as i32, board_size as i32, false, Format::RGB8) else {
+            godot_error!("Could not create the board image");
+            return;
+        };
 
         let square_size_vec = Vector2i {
             x: square_size_int,
             y: square_size_int,
         };
-        let light_color = Color::from_html(LIGHT_SQUARE_COLOR).unwrap();
-        image.fill(Color::from_html(DARK_SQUARE_COLOR).unwrap());
+        image.fill(self.dark_color);
         for row in 0..8 {
             let x = row * square_size_int;
             for column in 0..8 {
@@ -52,19 +97,15 @@ impl ITextureRect for GodotBoard {
                         },
                         size: square_size_vec,
                     },
-                    light_color,
+                    self.light_color,
                 );
             }
         }
 
-        let texture = ImageTexture::create_from_image(&image).unwrap();
-        base.set_texture(&texture);
-    }
-}
-
-impl GodotBoard {
-    pub fn set_square_size(&mut self, size: f32) {
-        self.square_size = size;
+        match ImageTexture::create_from_image(&image) {
+            Some(texture) => self.base_mut().set_texture(&texture),
+            None => godot_error!("Could not create the board texture"),
+        }
     }
 }
 
@@ -90,6 +131,12 @@ impl IReferenceRect for GodotSelectSquare {
     }
 }
 
+impl GodotSelectSquare {
+    pub fn set_border_color_html(&mut self, color: &str) {
+        self.base_mut().set_border_color(Color::from_html(color).unwrap());
+    }
+}
+
 #[derive(GodotClass)]
 #[class(base=Polygon2D)]
 pub struct LegalMoveHelper {
@@ -107,7 +154,7 @@ impl LegalMoveHelper {
     const ANGLE_IN_RAD: f32 = ((f64::consts::PI / 180.) * 30.) as f32;
     const SQUARE_FRACTION: f32 = 1. / 5.;
 
-    pub fn create(&mut self, position: Vector2, square_size: f32) {
+    pub fn create(&mut self, position: Vector2, square_size: f32, color: &str) {
         let width = square_size * Self::SQUARE_FRACTION;
         let height = Self::ANGLE_IN_RAD.cos() * width;
         let x_offset = Self::ANGLE_IN_RAD.sin() * width;
@@ -125,17 +172,133 @@ impl LegalMoveHelper {
         ];
 
         self.base_mut().set_polygon(&PackedVector2Array::from_iter(points));
-        self.base_mut().set_color(Color::from_html(LEGAL_MOVE_HELPER_COLOR).unwrap());
+        self.base_mut().set_color(Color::from_html(color).unwrap());
+
+        self.base_mut().set_visible(true);
+    }
+}
+
+#[derive(GodotClass)]
+#[class(base=Polygon2D)]
+pub struct HintArrow {
+    base: Base<Polygon2D>,
+}
+
+#[godot_api]
+impl IPolygon2D for HintArrow {
+    fn init(base: Base<Polygon2D>) -> Self {
+        Self { base }
+    }
+}
+
+impl HintArrow {
+    const SHAFT_FRACTION: f32 = 0.15;
+    const HEAD_WIDTH_FRACTION: f32 = 0.45;
+    const HEAD_LENGTH_FRACTION: f32 = 0.35;
+
+    /// Points from the centre of the `from` square to the centre of the `to` square
+    pub fn point(&mut self, from: Vector2, to: Vector2, square_size: f32, color: &str) {
+        let half_square = Vector2::new(square_size / 2., square_size / 2.);
+        let start = from + half_square;
+        let end = to + half_square;
+        let direction = (end - start).normalized();
+        let normal = Vector2::new(-direction.y, direction.x);
 
+        let shaft = normal * (square_size * Self::SHAFT_FRACTION / 2.);
+        let head = normal * (square_size * Self::HEAD_WIDTH_FRACTION / 2.);
+        let neck = end - direction * (square_size * Self::HEAD_LENGTH_FRACTION);
+
+        let points = [
+            start + shaft,
+            neck + shaft,
+            neck + head,
+            end,
+            neck - head,
+            neck - shaft,
+            start - shaft,
+        ];
+
+        self.base_mut().set_polygon(&PackedVector2Array::from_iter(points));
+        self.base_mut().set_color(Color::from_html(color).unwrap());
         self.base_mut().set_visible(true);
     }
 }
 
+/// Vertical bar split between White and Black by the evaluation, with the score written beside it
+#[derive(GodotClass)]
+#[class(base=ColorRect)]
+pub struct EvalBar {
+    readout: Gd<Label>,
+    white_at_bottom: bool,
+    white_fill: Gd<ColorRect>,
+    white_fraction: f32,
+    base: Base<ColorRect>,
+}
+
+#[godot_api]
+impl IColorRect for EvalBar {
+    fn init(base: Base<ColorRect>) -> Self {
+        Self {
+            readout: Label::new_alloc(),
+            white_at_bottom: true,
+            white_fill: ColorRect::new_alloc(),
+            white_fraction: 0.5,
+            base,
+        }
+    }
+
+    fn on_notification(&mut self, what: ControlNotification) {
+        if what == ControlNotification::PREDELETE {
+            free_if_orphaned(self.readout.clone());
+            free_if_orphaned(self.white_fill.clone());
+        }
+    }
+}
+
+impl EvalBar {
+    /// `readout_position` is relative to the bar
+    pub fn set(&mut self, position: Vector2, size: Vector2, readout_position: Vector2) {
+        self.base_mut().set_position(position);
+        self.base_mut().set_size(size);
+        self.base_mut().set_color(Color::from_html(EVAL_BAR_BLACK_COLOR).unwrap());
+
+        let mut white_fill = self.white_fill.clone();
+        white_fill.set_color(Color::from_html(EVAL_BAR_WHITE_COLOR).unwrap());
+        self.base_mut().add_child(&white_fill);
+
+        let mut readout = self.readout.clone();
+        readout.set_position(readout_position);
+        self.base_mut().add_child(&readout);
+
+        self.show_evaluation(0.5, "");
+    }
+
+    /// White's share grows from White's side of the board
+    pub fn set_orientation(&mut self, player_color: &GodotPieceColor) {
+        self.white_at_bottom = *player_color == GodotPieceColor::White;
+        let (white_fraction, text) = (self.white_fraction, self.readout.get_text().to_string());
+        self.show_evaluation(white_fraction, &text);
+    }
+
+    pub fn show_evaluation(&mut self, white_fraction: f32, text: &str) {
+        self.white_fraction = white_fraction.clamp(0., 1.);
+        let size = self.base().get_size();
+        let white_height = size.y * self.white_fraction;
+        let y = if self.white_at_bottom { size.y - white_height } else { 0. };
+        self.white_fill.set_position(Vector2::new(0., y));
+        self.white_fill.set_size(Vector2::new(size.x, white_height));
+        self.readout.set_text(text);
+    }
+}
+
 #[derive(GodotClass)]
 #[class(base=ColorRect)]
 pub struct PromotionRect {
-    color: GodotPieceColor,
+    highlight: Gd<GodotSelectSquare>,
+    highlighted_index: usize,
+    opens_upward: bool,
     pieces: [Gd<GodotPiece>; 4],
+    square: Option<GodotSquare>,
     base: Base<ColorRect>,
 }
 
@@ -143,52 +306,131 @@ pub struct PromotionRect {
 impl IColorRect for PromotionRect {
     fn init(base: Base<ColorRect>) -> Self {
         Self {
-            color: GodotPieceColor::White,
+            highlight: GodotSelectSquare::new_alloc(),
+            highlighted_index: 0,
+            opens_upward: false,
             pieces: [
                 GodotPiece::new_alloc(),
                 GodotPiece::new_alloc(),
                 GodotPiece::new_alloc(),
                 GodotPiece::new_alloc(),
             ],
+            square: None,
             base,
         }
     }
+
+    fn on_notification(&mut self, what: ControlNotification) {
+        if what == ControlNotification::PREDELETE {
+            free_if_orphaned(self.highlight.clone());
+            for piece in self.pieces.iter() {
+                free_if_orphaned(piece.clone());
+            }
+        }
+    }
 }
 
 impl PromotionRect {
-    const PROMOTION_PIECES: [GodotPieceKind; 4] = [
+    /// Ordered from the promotion square outwards
+    pub const PROMOTION_PIECES: [GodotPieceKind; 4] = [
         GodotPieceKind::Queen,
         GodotPieceKind::Knight,
         GodotPieceKind::Rook,
         GodotPieceKind::Bishop,
     ];
-    pub fn set(&mut self, color: GodotPieceColor, square_size: f32) {
-        self.color = color;
+
+    pub fn set(&mut self, square_size: f32) {
         self.base_mut().set_size(Vector2::new(square_size, square_size * 4.));
         self.base_mut().set_color(Color::GRAY);
         self.add_pieces(square_size);
+        self.add_highlight(square_size);
         self.base_mut().set_visible(false);
     }
+
     fn add_pieces(&mut self, square_size: f32) {
         for (i, piece_kind) in Self::PROMOTION_PIECES.into_iter().enumerate() {
-            let mut piece = GodotPiece::new_alloc();
-            piece.bind_mut().set_piece(piece_kind, self.color, square_size);
-            piece.bind_mut().set_image();
+            let mut piece = self.pieces[i].clone();
+            piece.bind_mut().set_piece(piece_kind, GodotPieceColor::White, square_size);
             piece.set_visible(false);
-            piece.set_position(Vector2::new(0., square_size * i as f32));
             self.base_mut().add_child(&piece);
-            self.pieces[i] = piece;
         }
     }
 
-    pub fn show(&mut self, square: &GodotSquare, square_size: f32) {
-        self.base_mut().set_visible(true);
-        let color = self.color;
-        let rectangle_position = square.get_ui_vector2(square_size, &color);
+    fn add_highlight(&mut self, square_size: f32) {
+        let mut highlight = self.highlight.clone();
+        highlight.set_size(Vector2::new(square_size, square_size));
+        self.base_mut().add_child(&highlight);
+    }
+
+    /// Opens toward the board centre, so the picker stays on the board for either side and orientation
+    pub fn show(&mut self, square: &GodotSquare, color: GodotPieceColor, square_size: f32, player_color: &GodotPieceColor) {
+        let row = square.get_field_index(player_color) / 8;
+        self.opens_upward = row > 3;
+        self.square = Some(*square);
+
+        let mut rectangle_position = square.get_ui_vector2(square_size, player_color);
+        if self.opens_upward {
+            rectangle_position.y -= square_size * 3.;
+        }
         self.base_mut().set_position(rectangle_position);
+        self.base_mut().set_visible(true);
+
+        let opens_upward = self.opens_upward;
+        for (i, piece) in self.pieces.iter_mut().enumerate() {
+            let slot = if opens_upward { 3 - i } else { i };
+            piece.bind_mut().set_piece(Self::PROMOTION_PIECES[i], color, square_size);
+            piece.bind_mut().set_image();
+            piece.set_position(Vector2::new(0., square_size * slot as f32));
+            piece.set_visible(true);
+        }
+        self.highlight_piece(0, square_size);
+    }
+
+    pub fn close(&mut self) {
+        self.square = None;
+        self.base_mut().set_visible(false);
+        self.highlight.set_visible(false);
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.square.is_some()
+    }
 
-        for piece in self.pieces.iter_mut() {
-            piece.set_visible(true)
+    /// Index into `PROMOTION_PIECES` of the clicked piece, or `None` if the click missed the picker
+    pub fn get_clicked_index(&self, clicked_square: &GodotSquare, player_color: &GodotPieceColor) -> Option<usize> {
+        let square = self.square?;
+        let origin = square.get_field_index(player_color) as i16;
+        let clicked = clicked_square.get_field_index(player_color) as i16;
+        if origin % 8 != clicked % 8 {
+            return None;
         }
+
+        let offset = if self.opens_upward {
+            origin / 8 - clicked / 8
+        } else {
+            clicked / 8 - origin / 8
+        };
+        (0..4).contains(&offset).then_some(offset as usize)
+    }
+
+    pub fn get_highlighted_index(&self) -> usize {
+        self.highlighted_index
+    }
+
+    /// Moves the keyboard highlight by `step` pieces away from the promotion square
+    pub fn move_highlight(&mut self, step: i16, square_size: f32) {
+        let index = (self.highlighted_index as i16 + step).clamp(0, 3) as usize;
+        self.highlight_piece(index, square_size);
+    }
+
+    pub fn highlight_piece(&mut self, index: usize, square_size: f32) {
+        self.highlighted_index = index;
+        let slot = if self.opens_upward { 3 - index } else { index };
+        self.highlight.set_position(Vector2::new(0., square_size * slot as f32));
+        self.highlight.set_visible(true);
+    }
+
+    pub fn opens_upward(&self) -> bool {
+        self.opens_upward
     }
 }
@@ -1,11 +1,16 @@
 use std::fmt::Display;
 
+#[cfg(feature = "godot")]
+use godot::classes::image::Format;
+#[cfg(feature = "godot")]
 use godot::classes::{ITextureRect, Image, ImageTexture, TextureRect};
+#[cfg(feature = "godot")]
 use godot::prelude::*;
 
+#[cfg(feature = "godot")]
 use crate::consts::*;
 
-#[derive(PartialEq, Eq, Clone, Copy)]
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
 #[repr(u8)]
 pub enum GodotPieceKind {
     Pawn,
@@ -26,13 +31,47 @@ impl Display for GodotPieceKind {
             Self::Rook => "r",
             Self::Queen => "q",
             Self::King => "k",
-            Self::Na => panic!("Asking to display NA kind"),
+            Self::Na => "?",
         };
         write!(f, "{}", output)
     }
 }
 
-#[derive(PartialEq, Eq, Clone, Copy)]
+impl GodotPieceKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Pawn => "pawn",
+            Self::Knight => "knight",
+            Self::Bishop => "bishop",
+            Self::Rook => "rook",
+            Self::Queen => "queen",
+            Self::King => "king",
+            Self::Na => "piece",
+        }
+    }
+
+    /// FEN letter, upper case for white
+    pub fn get_fen_char(&self, color: GodotPieceColor) -> char {
+        let letter = self.to_string().chars().next().unwrap_or('?');
+        match color {
+            GodotPieceColor::White => letter.to_ascii_uppercase(),
+            GodotPieceColor::Black => letter,
+        }
+    }
+
+    /// Promotion piece index as encoded in the engine's moves
+    pub fn get_promotion_piece_n(&self) -> usize {
+        match self {
+            Self::Queen => 0,
+            Self::Rook => 1,
+            Self::Bishop => 2,
+            Self::Knight => 3,
+            _ => 0,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
 pub enum GodotPieceColor {
     White,
     Black,
@@ -45,6 +84,21 @@ impl GodotPieceColor {
             Self::Black => Self::White,
         }
     }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::White => "white",
+            Self::Black => "black",
+        }
+    }
+
+    /// PGN result of a game won by this color
+    pub fn get_win_result(&self) -> &'static str {
+        match self {
+            Self::White => "1-0",
+            Self::Black => "0-1",
+        }
+    }
 }
 
 impl Display for GodotPieceColor {
@@ -57,6 +111,7 @@ impl Display for GodotPieceColor {
     }
 }
 
+#[cfg(feature = "godot")]
 #[derive(GodotClass)]
 #[class(base=TextureRect)]
 pub struct GodotPiece {
@@ -66,6 +121,7 @@ pub struct GodotPiece {
     base: Base<TextureRect>,
 }
 
+#[cfg(feature = "godot")]
 #[godot_api]
 impl ITextureRect for GodotPiece {
     fn init(base: Base<TextureRect>) -> Self {
@@ -78,6 +134,7 @@ impl ITextureRect for GodotPiece {
     }
 }
 
+#[cfg(feature = "godot")]
 impl GodotPiece {
     pub fn set_piece(&mut self, kind: GodotPieceKind, color: GodotPieceColor, size: f32) {
         self.image_file_name = format!("{}{}.png", kind, color);
@@ -87,9 +144,38 @@ impl GodotPiece {
         self.base_mut().set_size(Vector2::new(size, size));
     }
 
+    /// Falls back to a plain placeholder in the piece's color when the image can't be loaded
     pub fn set_image(&mut self) {
-        let image = Image::load_from_file(&format!("{}{}", RESOURCES_FOLDER_PATH, self.image_file_name)).unwrap();
-        let texture = ImageTexture::create_from_image(&image).unwrap();
-        self.base_mut().set_texture(&texture);
+        let path = format!("{}{}", RESOURCES_FOLDER_PATH, self.image_file_name);
+        let texture = Image::load_from_file(&path)
+            .and_then(|image| ImageTexture::create_from_image(&image))
+            .or_else(|| {
+                godot_error!("Could not load piece image '{}', using a placeholder", path);
+                self.create_placeholder_texture()
+            });
+        if let Some(texture) = texture {
+            self.base_mut().set_texture(&texture);
+        }
+    }
+
+    fn create_placeholder_texture(&self) -> Option<Gd<ImageTexture>> {
+        let mut image = Image::create_empty(PLACEHOLDER_PIECE_SIZE, PLACEHOLDER_PIECE_SIZE, false, Format::RGBA8)?;
+        let color = match self.color {
+            GodotPieceColor::White => Color::WHITE,
+            GodotPieceColor::Black => Color::BLACK,
+        };
+        let margin = PLACEHOLDER_PIECE_SIZE / 4;
+        image.fill(Color::TRANSPARENT_BLACK);
+        image.fill_rect(
+            Rect2i {
+                position: Vector2i { x: margin, y: margin },
+                size: Vector2i {
+                    x: PLACEHOLDER_PIECE_SIZE - 2 * margin,
+                    y: PLACEHOLDER_PIECE_SIZE - 2 * margin,
+                },
+            },
+            color,
+        );
+        ImageTexture::create_from_image(&image)
     }
 }
@@ -0,0 +1,82 @@
+use crate::chess_pieces::GodotPieceColor;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TimeControl {
+    pub base_seconds: f64,
+    pub increment_seconds: f64,
+}
+
+impl TimeControl {
+    /// Parses `minutes+increment_seconds`, e.g. `5+3`. Anything else means the game is untimed.
+    pub fn parse(time_control: &str) -> Option<Self> {
+        let (minutes, increment) = time_control.trim().split_once('+')?;
+        let base_seconds = minutes.trim().parse::<f64>().ok()? * 60.;
+        let increment_seconds = increment.trim().parse::<f64>().ok()?;
+        if base_seconds <= 0. || increment_seconds < 0. {
+            return None;
+        }
+        Some(Self {
+            base_seconds,
+            increment_seconds,
+        })
+    }
+}
+
+pub struct GameClock {
+    increment_seconds: f64,
+    low_time_warned: [bool; 2],
+    /// White, black
+    remaining_seconds: [f64; 2],
+}
+
+impl GameClock {
+    pub fn new(time_control: TimeControl) -> Self {
+        Self {
+            increment_seconds: time_control.increment_seconds,
+            low_time_warned: [false; 2],
+            remaining_seconds: [time_control.base_seconds; 2],
+        }
+    }
+
+    fn index(color: &GodotPieceColor) -> usize {
+        match color {
+            GodotPieceColor::White => 0,
+            GodotPieceColor::Black => 1,
+        }
+    }
+
+    /// Runs the clock of the side to move. Returns true once its time is up.
+    pub fn tick(&mut self, turn: &GodotPieceColor, delta_seconds: f64) -> bool {
+        let remaining = &mut self.remaining_seconds[Self::index(turn)];
+        *remaining = (*remaining - delta_seconds).max(0.);
+        *remaining == 0.
+    }
+
+    /// True the first time `color` drops below `threshold_seconds`
+    pub fn take_low_time_warning(&mut self, color: &GodotPieceColor, threshold_seconds: f64) -> bool {
+        let index = Self::index(color);
+        if self.low_time_warned[index] || self.remaining_seconds[index] >= threshold_seconds {
+            return false;
+        }
+        self.low_time_warned[index] = true;
+        true
+    }
+
+    /// Called when `color` completes a move
+    pub fn press(&mut self, color: &GodotPieceColor) {
+        self.remaining_seconds[Self::index(color)] += self.increment_seconds;
+    }
+
+    pub fn get_remaining_seconds(&self, color: &GodotPieceColor) -> f64 {
+        self.remaining_seconds[Self::index(color)]
+    }
+
+    pub fn get_increment_seconds(&self) -> f64 {
+        self.increment_seconds
+    }
+
+    pub fn format(seconds: f64) -> String {
+        let seconds = seconds.ceil() as u64;
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
@@ -3,7 +3,60 @@ pub const DARK_SQUARE_COLOR: &str = "#bb5746";
 pub const SELECT_BORDER_COLOR: &str = "#403e39";
 pub const LEGAL_MOVE_HELPER_COLOR: &str = "#00000040"; //"#9e9c9580"
 pub const RESOURCES_FOLDER_PATH: &str = "res://resources/";
-pub const ENGINE_MOVE_FOLDER_PATH: &str = "./data//moves/";
+pub const ENGINE_MOVE_FOLDER_PATH: &str = "res://data/moves/";
+pub const ENGINE_MOVE_CACHE_FOLDER_PATH: &str = "user://engine_tables/";
+/// The move tables in the source tree, for running the engine outside Godot
+pub const SOURCE_MOVE_FOLDER_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../godot/data/moves/");
 pub const MOVE_SOUND_FILE_NAME: &str = "move.ogg";
 pub const CAPTURE_SOUND_FILE_NAME: &str = "capture.ogg";
 pub const SOUNDS_SUBFOLDER_PATH: &str = "sounds/";
+// Okabe-Ito palette, distinguishable with the common forms of colour blindness
+pub const HIGH_CONTRAST_SELECT_BORDER_COLOR: &str = "#0072b2";
+pub const HIGH_CONTRAST_FOCUS_BORDER_COLOR: &str = "#e69f00";
+pub const HIGH_CONTRAST_LEGAL_MOVE_HELPER_COLOR: &str = "#000000c0";
+pub const FOCUS_BORDER_COLOR: &str = "#3a7bd5";
+pub const TTS_LANGUAGE: &str = "en";
+pub const SETTINGS_FILE_PATH: &str = "user://settings.cfg";
+pub const SETTINGS_SECTION: &str = "settings";
+pub const DEFAULT_THEME: &str = "classic";
+// name, light square, dark square
+pub const BOARD_THEMES: [(&str, &str, &str); 4] = [
+    ("classic", LIGHT_SQUARE_COLOR, DARK_SQUARE_COLOR),
+    ("green", "#eeeed2", "#769656"),
+    ("blue", "#dee3e6", "#8ca2ad"),
+    ("high_contrast", "#ffffff", "#5a5a5a"),
+];
+pub const BASE_ANIMATION_DURATION: f64 = 0.15;
+pub const BOT_MOVE_DELAY_SECONDS: f64 = 0.3;
+/// Bot levels are the search depth in plies
+pub const MIN_BOT_LEVEL: i32 = 1;
+pub const MAX_BOT_LEVEL: i32 = 10;
+/// Level of new games and of the UCI and terminal versions of the bot
+pub const DEFAULT_BOT_LEVEL: i32 = 3;
+pub const PLACEHOLDER_PIECE_SIZE: i32 = 64;
+pub const CHECK_SOUND_FILE_NAME: &str = "check.ogg";
+pub const CASTLE_SOUND_FILE_NAME: &str = "castle.ogg";
+pub const PROMOTE_SOUND_FILE_NAME: &str = "promote.ogg";
+pub const ILLEGAL_MOVE_SOUND_FILE_NAME: &str = "illegal.ogg";
+pub const GAME_START_SOUND_FILE_NAME: &str = "game_start.ogg";
+pub const GAME_END_SOUND_FILE_NAME: &str = "game_end.ogg";
+pub const LOW_TIME_SOUND_FILE_NAME: &str = "low_time.ogg";
+pub const USER_SOUND_PACK_FOLDER_PATH: &str = "user://sounds/";
+pub const LOW_TIME_THRESHOLD_SECONDS: f64 = 10.;
+pub const HINT_ARROW_COLOR: &str = "#15781bc0";
+pub const HIGH_CONTRAST_HINT_ARROW_COLOR: &str = "#009e73e0";
+pub const EVAL_BAR_WHITE_COLOR: &str = "#f0f0f0";
+pub const EVAL_BAR_BLACK_COLOR: &str = "#303030";
+pub const EVAL_BAR_WIDTH: f32 = 16.;
+/// Plies searched for the eval bar, shallow enough to keep up with fast games
+pub const EVAL_SEARCH_DEPTH: u8 = 4;
+pub const ANALYSIS_CACHE_FOLDER_PATH: &str = "user://analysis/";
+/// Plies searched per position in post-game analysis
+pub const ANALYSIS_DEPTH: u8 = 5;
+/// Deepest search after each root move of the multi-PV search, which searches every legal move at each depth
+pub const MULTI_PV_MAX_DEPTH: u8 = 6;
+pub const GHOST_PIECE_ALPHA: f32 = 0.45;
+/// Thinking time per move for UCI engines in untimed games
+pub const UCI_MOVE_TIME_MS: u64 = 1_000;
+/// A small Polyglot book of main line openings shipped with the game
+pub const DEFAULT_BOOK_FILE_PATH: &str = "res://data/book.bin";
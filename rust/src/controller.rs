@@ -0,0 +1,489 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::chess_pieces::{GodotPieceColor, GodotPieceKind};
+use crate::moves::GodotMove;
+use crate::square::GodotSquare;
+
+/// A change the view should make after an input, in order
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ViewCommand {
+    /// Mark `square` as selected and show where its piece can go
+    Select {
+        square: GodotSquare,
+        destinations: Vec<GodotSquare>,
+    },
+    ClearSelection,
+    /// Ask which piece to promote to on `square`
+    OpenPromotion(GodotSquare),
+    ClosePromotion,
+    /// Apply a legal move. The view reports the resulting position back through `GameController::set_position`.
+    PlayMove(GodotMove),
+    IllegalMove {
+        from: GodotSquare,
+        to: GodotSquare,
+    },
+}
+
+/// The select, move and promote state machine behind the board, without any Godot nodes
+pub struct GameController {
+    auto_queen: bool,
+    game_over: bool,
+    legal_moves: HashMap<GodotSquare, HashSet<GodotMove>>,
+    /// Origin and destination of a promotion waiting for the piece choice
+    pending_promotion: Option<(GodotSquare, GodotSquare)>,
+    selected_square: Option<GodotSquare>,
+    turn: GodotPieceColor,
+}
+
+impl Default for GameController {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GameController {
+    pub fn new() -> Self {
+        Self {
+            auto_queen: false,
+            game_over: false,
+            legal_moves: HashMap::new(),
+            pending_promotion: None,
+            selected_square: None,
+            turn: GodotPieceColor::White,
+        }
+    }
+
+    /// Call whenever the position changes. Drops the selection and any pending promotion.
+    pub fn set_position(&mut self, turn: GodotPieceColor, legal_moves: HashMap<GodotSquare, HashSet<GodotMove>>) {
+        self.turn = turn;
+        self.legal_moves = legal_moves;
+        self.selected_square = None;
+        self.pending_promotion = None;
+    }
+
+    pub fn set_game_over(&mut self, game_over: bool) {
+        self.game_over = game_over;
+        if game_over {
+            self.selected_square = None;
+            self.pending_promotion = None;
+        }
+    }
+
+    pub fn set_auto_queen(&mut self, auto_queen: bool) {
+        self.auto_queen = auto_queen;
+    }
+
+    pub fn is_game_over(&self) -> bool {
+        self.game_over
+    }
+
+    pub fn get_turn(&self) -> GodotPieceColor {
+        self.turn
+    }
+
+    pub fn get_legal_moves(&self) -> &HashMap<GodotSquare, HashSet<GodotMove>> {
+        &self.legal_moves
+    }
+
+    #[cfg(test)]
+    pub fn get_selected_square(&self) -> Option<GodotSquare> {
+        self.selected_square
+    }
+
+    /// Destination of the promotion waiting for a piece choice
+    pub fn get_pending_promotion(&self) -> Option<GodotSquare> {
+        self.pending_promotion.map(|(_, destination)| destination)
+    }
+
+    /// Destinations of the legal moves from `square`, with the four promotion moves to a square counted once
+    pub fn get_destinations(&self, square: &GodotSquare) -> Vec<GodotSquare> {
+        let Some(moves) = self.legal_moves.get(square) else {
+            return Vec::new();
+        };
+        let mut output: Vec<GodotSquare> = Vec::with_capacity(moves.len());
+        for legal_move in moves {
+            let destination = legal_move.get_destination();
+            if !output.contains(&destination) {
+                output.push(destination);
+            }
+        }
+        output
+    }
+
+    /// A click or key press on `square`, holding a piece of `piece_color` if any.
+    /// While a promotion is pending, use `choose_promotion` or `cancel_promotion` instead; other squares cancel it.
+    pub fn click(&mut self, square: GodotSquare, piece_color: Option<GodotPieceColor>) -> Vec<ViewCommand> {
+        if self.game_over {
+            return Vec::new();
+        }
+        if self.pending_promotion.is_some() {
+            return self.cancel_promotion();
+        }
+
+        // Select, or change the selection to another piece of the side to move
+        if piece_color == Some(self.turn) {
+            self.selected_square = Some(square);
+            return vec![ViewCommand::Select {
+                square,
+                destinations: self.get_destinations(&square),
+            }];
+        }
+
+        let Some(origin) = self.selected_square else {
+            return Vec::new();
+        };
+        let candidates: Vec<GodotMove> = self
+            .legal_moves
+            .get(&origin)
+            .into_iter()
+            .flatten()
+            .filter(|legal_move| legal_move.get_destination() == square)
+            .copied()
+            .collect();
+
+        match candidates.first() {
+            // Clicking an empty square the piece can't reach only drops the selection
+            None if piece_color.is_none() => {
+                self.selected_square = None;
+                vec![ViewCommand::ClearSelection]
+            }
+            None => {
+                self.selected_square = None;
+                vec![
+                    ViewCommand::ClearSelection,
+                    ViewCommand::IllegalMove {
+                        from: origin,
+                        to: square,
+                    },
+                ]
+            }
+            Some(legal_move) if legal_move.is_promotion() => {
+                self.pending_promotion = Some((origin, square));
+                if self.auto_queen {
+                    return self.choose_promotion(GodotPieceKind::Queen);
+                }
+                vec![ViewCommand::OpenPromotion(square)]
+            }
+            Some(legal_move) => {
+                self.selected_square = None;
+                vec![ViewCommand::ClearSelection, ViewCommand::PlayMove(*legal_move)]
+            }
+        }
+    }
+
+    pub fn choose_promotion(&mut self, kind: GodotPieceKind) -> Vec<ViewCommand> {
+        let Some((origin, destination)) = self.pending_promotion.take() else {
+            return Vec::new();
+        };
+        self.selected_square = None;
+
+        let mut output = vec![ViewCommand::ClosePromotion, ViewCommand::ClearSelection];
+        let chosen_move = self.legal_moves.get(&origin).into_iter().flatten().find(|legal_move| {
+            legal_move.get_destination() == destination
+                && legal_move.is_promotion()
+                && legal_move.get_promotion_piece() == kind.get_promotion_piece_n()
+        });
+        if let Some(legal_move) = chosen_move {
+            output.push(ViewCommand::PlayMove(*legal_move));
+        }
+        output
+    }
+
+    pub fn cancel_promotion(&mut self) -> Vec<ViewCommand> {
+        if self.pending_promotion.take().is_none() {
+            return Vec::new();
+        }
+        self.selected_square = None;
+        vec![ViewCommand::ClosePromotion, ViewCommand::ClearSelection]
+    }
+
+    /// Plays a move chosen without the board, e.g. by a script, dropping the selection and any pending promotion.
+    /// Nothing happens if the move isn't legal.
+    pub fn play_move(&mut self, chosen_move: GodotMove) -> Vec<ViewCommand> {
+        let is_legal = self
+            .legal_moves
+            .get(&chosen_move.get_origin())
+            .is_some_and(|moves| moves.contains(&chosen_move));
+        if self.game_over || !is_legal {
+            return Vec::new();
+        }
+        let mut output = self.deselect();
+        output.push(ViewCommand::PlayMove(chosen_move));
+        output
+    }
+
+    pub fn deselect(&mut self) -> Vec<ViewCommand> {
+        if self.pending_promotion.is_some() {
+            return self.cancel_promotion();
+        }
+        self.selected_square = None;
+        vec![ViewCommand::ClearSelection]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(name: &str) -> GodotSquare {
+        GodotSquare::from_name(name).unwrap()
+    }
+
+    fn quiet_move(origin: &str, destination: &str) -> GodotMove {
+        GodotMove::from_origin_and_destination(&square(origin), &square(destination))
+    }
+
+    fn promotion_move(origin: &str, destination: &str, kind: GodotPieceKind) -> GodotMove {
+        GodotMove::from_origin_destination_and_promotion(&square(origin), &square(destination), kind.get_promotion_piece_n())
+    }
+
+    fn controller_with_moves(moves: &[GodotMove]) -> GameController {
+        let mut legal_moves: HashMap<GodotSquare, HashSet<GodotMove>> = HashMap::new();
+        for legal_move in moves {
+            legal_moves.entry(legal_move.get_origin()).or_default().insert(*legal_move);
+        }
+        let mut controller = GameController::new();
+        controller.set_position(GodotPieceColor::White, legal_moves);
+        controller
+    }
+
+    fn promotion_controller() -> GameController {
+        controller_with_moves(
+            &[
+                GodotPieceKind::Queen,
+                GodotPieceKind::Rook,
+                GodotPieceKind::Bishop,
+                GodotPieceKind::Knight,
+            ]
+            .map(|kind| promotion_move("a7", "a8", kind)),
+        )
+    }
+
+    #[test]
+    fn selecting_own_piece_shows_destinations() {
+        let mut controller = controller_with_moves(&[quiet_move("e2", "e3"), quiet_move("e2", "e4")]);
+
+        let commands = controller.click(square("e2"), Some(GodotPieceColor::White));
+
+        let [
+            ViewCommand::Select {
+                square: selected,
+                destinations,
+            },
+        ] = commands.as_slice()
+        else {
+            panic!("unexpected commands {:?}", commands);
+        };
+        assert_eq!(*selected, square("e2"));
+        assert_eq!(destinations.len(), 2);
+        assert!(destinations.contains(&square("e3")) && destinations.contains(&square("e4")));
+        assert_eq!(controller.get_selected_square(), Some(square("e2")));
+    }
+
+    #[test]
+    fn clicks_without_selection_or_on_opponent_pieces_do_nothing() {
+        let mut controller = controller_with_moves(&[quiet_move("e2", "e4")]);
+
+        assert!(controller.click(square("e4"), None).is_empty());
+        assert!(controller.click(square("e7"), Some(GodotPieceColor::Black)).is_empty());
+        assert_eq!(controller.get_selected_square(), None);
+    }
+
+    #[test]
+    fn reselecting_switches_selection() {
+        let mut controller = controller_with_moves(&[quiet_move("e2", "e4"), quiet_move("g1", "f3")]);
+
+        controller.click(square("e2"), Some(GodotPieceColor::White));
+        let commands = controller.click(square("g1"), Some(GodotPieceColor::White));
+
+        assert_eq!(
+            commands,
+            vec![ViewCommand::Select {
+                square: square("g1"),
+                destinations: vec![square("f3")],
+            }]
+        );
+        assert_eq!(controller.get_selected_square(), Some(square("g1")));
+    }
+
+    #[test]
+    fn legal_destination_plays_move() {
+        let e2e4 = quiet_move("e2", "e4");
+        let mut controller = controller_with_moves(&[e2e4]);
+
+        controller.click(square("e2"), Some(GodotPieceColor::White));
+        let commands = controller.click(square("e4"), None);
+
+        assert_eq!(commands, vec![ViewCommand::ClearSelection, ViewCommand::PlayMove(e2e4)]);
+        assert_eq!(controller.get_selected_square(), None);
+    }
+
+    #[test]
+    fn illegal_capture_is_reported_and_drops_selection() {
+        let mut controller = controller_with_moves(&[quiet_move("e2", "e4")]);
+
+        controller.click(square("e2"), Some(GodotPieceColor::White));
+        let commands = controller.click(square("e7"), Some(GodotPieceColor::Black));
+
+        assert_eq!(
+            commands,
+            vec![
+                ViewCommand::ClearSelection,
+                ViewCommand::IllegalMove {
+                    from: square("e2"),
+                    to: square("e7"),
+                },
+            ]
+        );
+        assert_eq!(controller.get_selected_square(), None);
+    }
+
+    #[test]
+    fn unreachable_empty_square_only_deselects() {
+        let mut controller = controller_with_moves(&[quiet_move("e2", "e4")]);
+
+        controller.click(square("e2"), Some(GodotPieceColor::White));
+        let commands = controller.click(square("e5"), None);
+
+        assert_eq!(commands, vec![ViewCommand::ClearSelection]);
+        assert_eq!(controller.get_selected_square(), None);
+    }
+
+    #[test]
+    fn promotion_waits_for_piece_choice() {
+        let mut controller = promotion_controller();
+
+        controller.click(square("a7"), Some(GodotPieceColor::White));
+        let commands = controller.click(square("a8"), None);
+        assert_eq!(commands, vec![ViewCommand::OpenPromotion(square("a8"))]);
+        assert_eq!(controller.get_pending_promotion(), Some(square("a8")));
+
+        let commands = controller.choose_promotion(GodotPieceKind::Knight);
+        assert_eq!(
+            commands,
+            vec![
+                ViewCommand::ClosePromotion,
+                ViewCommand::ClearSelection,
+                ViewCommand::PlayMove(promotion_move("a7", "a8", GodotPieceKind::Knight)),
+            ]
+        );
+        assert_eq!(controller.get_pending_promotion(), None);
+    }
+
+    #[test]
+    fn promotion_destination_is_listed_once() {
+        let controller = promotion_controller();
+
+        assert_eq!(controller.get_destinations(&square("a7")), vec![square("a8")]);
+    }
+
+    #[test]
+    fn auto_queen_skips_the_picker() {
+        let mut controller = promotion_controller();
+        controller.set_auto_queen(true);
+
+        controller.click(square("a7"), Some(GodotPieceColor::White));
+        let commands = controller.click(square("a8"), None);
+
+        assert_eq!(
+            commands.last(),
+            Some(&ViewCommand::PlayMove(promotion_move("a7", "a8", GodotPieceKind::Queen)))
+        );
+        assert!(!commands.contains(&ViewCommand::OpenPromotion(square("a8"))));
+    }
+
+    #[test]
+    fn clicking_elsewhere_cancels_promotion() {
+        let mut controller = promotion_controller();
+
+        controller.click(square("a7"), Some(GodotPieceColor::White));
+        controller.click(square("a8"), None);
+        let commands = controller.click(square("d4"), None);
+
+        assert_eq!(commands, vec![ViewCommand::ClosePromotion, ViewCommand::ClearSelection]);
+        assert_eq!(controller.get_pending_promotion(), None);
+        assert_eq!(controller.get_selected_square(), None);
+        assert!(controller.choose_promotion(GodotPieceKind::Queen).is_empty());
+    }
+
+    #[test]
+    fn deselect_clears_selection() {
+        let mut controller = controller_with_moves(&[quiet_move("e2", "e4")]);
+
+        controller.click(square("e2"), Some(GodotPieceColor::White));
+
+        assert_eq!(controller.deselect(), vec![ViewCommand::ClearSelection]);
+        assert!(controller.click(square("e4"), None).is_empty());
+    }
+
+    #[test]
+    fn game_over_ignores_input() {
+        let mut controller = controller_with_moves(&[quiet_move("e2", "e4")]);
+        controller.click(square("e2"), Some(GodotPieceColor::White));
+
+        controller.set_game_over(true);
+
+        assert_eq!(controller.get_selected_square(), None);
+        assert!(controller.click(square("e2"), Some(GodotPieceColor::White)).is_empty());
+    }
+
+    #[test]
+    fn new_position_drops_selection() {
+        let mut controller = controller_with_moves(&[quiet_move("e2", "e4")]);
+        controller.click(square("e2"), Some(GodotPieceColor::White));
+
+        controller.set_position(GodotPieceColor::Black, HashMap::new());
+
+        assert_eq!(controller.get_selected_square(), None);
+        assert_eq!(controller.get_turn(), GodotPieceColor::Black);
+        assert!(controller.click(square("e2"), Some(GodotPieceColor::White)).is_empty());
+    }
+
+    #[test]
+    fn direct_move_skips_promotion_choice() {
+        let knight_promotion = promotion_move("a7", "a8", GodotPieceKind::Knight);
+        let mut controller = promotion_controller();
+
+        let commands = controller.play_move(knight_promotion);
+
+        assert_eq!(
+            commands,
+            vec![ViewCommand::ClearSelection, ViewCommand::PlayMove(knight_promotion)]
+        );
+        assert_eq!(controller.get_pending_promotion(), None);
+    }
+
+    #[test]
+    fn direct_move_closes_pending_promotion() {
+        let e2e4 = quiet_move("e2", "e4");
+        let mut moves = [GodotPieceKind::Queen, GodotPieceKind::Knight]
+            .map(|kind| promotion_move("a7", "a8", kind))
+            .to_vec();
+        moves.push(e2e4);
+        let mut controller = controller_with_moves(&moves);
+        controller.click(square("a7"), Some(GodotPieceColor::White));
+        controller.click(square("a8"), None);
+
+        let commands = controller.play_move(e2e4);
+
+        assert_eq!(
+            commands,
+            vec![
+                ViewCommand::ClosePromotion,
+                ViewCommand::ClearSelection,
+                ViewCommand::PlayMove(e2e4)
+            ]
+        );
+        assert_eq!(controller.get_pending_promotion(), None);
+        assert_eq!(controller.get_selected_square(), None);
+    }
+
+    #[test]
+    fn direct_illegal_move_does_nothing() {
+        let mut controller = controller_with_moves(&[quiet_move("e2", "e4")]);
+        controller.click(square("e2"), Some(GodotPieceColor::White));
+
+        assert!(controller.play_move(quiet_move("e2", "e5")).is_empty());
+        assert_eq!(controller.get_selected_square(), Some(square("e2")));
+    }
+}
@@ -0,0 +1,383 @@
+use godot::classes::notify::Node2DNotification;
+use godot::classes::{INode2D, ITextureRect, InputEvent, InputEventMouseButton, InputEventMouseMotion, Label, Node2D};
+use godot::global::MouseButton;
+use godot::prelude::*;
+use rustier_chess::types::square::Square;
+
+use crate::chess_board::{GodotBoard, GodotSelectSquare, free_if_orphaned};
+use crate::chess_pieces::{GodotPiece, GodotPieceColor, GodotPieceKind};
+use crate::engine::ChessEngine;
+use crate::settings::GodotSettings;
+use crate::setup::{PositionSetup, SetupPiece};
+use crate::square::GodotSquare;
+
+const START_POSITION_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+#[derive(Clone, Copy)]
+enum DragOrigin {
+    Palette,
+    Board(GodotSquare),
+}
+
+/// Board editor for setting up custom positions. Squares are edited with a brush picked from the palette
+/// to the right of the board, either by clicking or by dragging.
+#[derive(GodotClass)]
+#[class(base=Node2D)]
+struct GodotPositionEditor {
+    board_background: Gd<GodotBoard>,
+    /// `None` is the eraser
+    brush: Option<SetupPiece>,
+    drag_origin: Option<DragOrigin>,
+    drag_piece: Option<Gd<GodotPiece>>,
+    orientation: GodotPieceColor,
+    palette_cursor: Gd<GodotSelectSquare>,
+    piece_nodes: Vec<Gd<GodotPiece>>,
+    setup: PositionSetup,
+    square_size: f32,
+    base: Base<Node2D>,
+}
+
+#[godot_api]
+impl INode2D for GodotPositionEditor {
+    fn init(base: Base<Node2D>) -> Self {
+        Self {
+            board_background: GodotBoard::new_alloc(),
+            brush: Some((GodotPieceColor::White, GodotPieceKind::Pawn)),
+            drag_origin: None,
+            drag_piece: None,
+            orientation: GodotPieceColor::White,
+            palette_cursor: GodotSelectSquare::new_alloc(),
+            piece_nodes: Vec::with_capacity(32),
+            setup: PositionSetup::default(),
+            square_size: 80.,
+            base,
+        }
+    }
+
+    fn on_notification(&mut self, what: Node2DNotification) {
+        if what == Node2DNotification::PREDELETE {
+            free_if_orphaned(self.board_background.clone());
+            free_if_orphaned(self.palette_cursor.clone());
+        }
+    }
+
+    fn input(&mut self, input_event: Gd<InputEvent>) {
+        if let Ok(motion_event) = input_event.clone().try_cast::<InputEventMouseMotion>() {
+            let half_square = Vector2::new(self.square_size / 2., self.square_size / 2.);
+            if let Some(drag_piece) = self.drag_piece.as_mut() {
+                drag_piece.set_position(motion_event.get_position() - half_square);
+            }
+            return;
+        }
+
+        let Ok(mouse_button_event) = input_event.try_cast::<InputEventMouseButton>() else {
+            return;
+        };
+        let position = mouse_button_event.get_position();
+
+        match (mouse_button_event.get_button_index(), mouse_button_event.is_pressed()) {
+            (MouseButton::LEFT, true) => self.start_drag(position),
+            (MouseButton::LEFT, false) => self.end_drag(position),
+            (MouseButton::RIGHT, true) => {
+                if let Some(square) = self.get_board_square(position) {
+                    self.setup.set_piece(&square, None);
+                    self.refresh_pieces();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[godot_api]
+impl GodotPositionEditor {
+    const PALETTE_KINDS: [GodotPieceKind; 6] = [
+        GodotPieceKind::King,
+        GodotPieceKind::Queen,
+        GodotPieceKind::Rook,
+        GodotPieceKind::Bishop,
+        GodotPieceKind::Knight,
+        GodotPieceKind::Pawn,
+    ];
+
+    #[func]
+    fn custom_ready(&mut self) {
+        let mut board = self.board_background.clone();
+        board.bind_mut().set_square_size(self.square_size);
+        board.bind_mut().ready();
+        board
+            .bind_mut()
+            .set_theme(&GodotSettings::load_settings().bind().theme.to_string());
+        self.base_mut().add_child(&board);
+
+        self.init_palette();
+        self.load_fen(START_POSITION_FEN.into());
+    }
+
+    /// Loads a position into the editor. Returns false and keeps the current position if the FEN can't be parsed.
+    #[func]
+    fn load_fen(&mut self, fen: String) -> bool {
+        let Ok(engine) = ChessEngine::from_fen(&fen) else {
+            return false;
+        };
+
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        self.setup = PositionSetup {
+            side_to_move: engine.get_turn(),
+            en_passant: fields.get(3).and_then(|square| GodotSquare::from_name(square)),
+            ..PositionSetup::default()
+        };
+        for (square, color, kind) in engine.get_pieces_per_square(&GodotPieceColor::White) {
+            self.setup.set_piece(&square, Some((color, kind)));
+        }
+        self.setup.set_castling_rights(fields.get(2).copied().unwrap_or("-"));
+
+        self.refresh_pieces();
+        true
+    }
+
+    #[func]
+    fn set_start_position(&mut self) {
+        self.load_fen(START_POSITION_FEN.into());
+    }
+
+    #[func]
+    fn clear(&mut self) {
+        self.setup = PositionSetup {
+            side_to_move: self.setup.side_to_move,
+            ..PositionSetup::default()
+        };
+        self.refresh_pieces();
+    }
+
+    #[func]
+    fn flip(&mut self) {
+        self.orientation = self.orientation.opponent_turn();
+        self.refresh_pieces();
+    }
+
+    #[func]
+    fn set_side_to_move(&mut self, color: String) {
+        self.setup.side_to_move = if color == "white" {
+            GodotPieceColor::White
+        } else {
+            GodotPieceColor::Black
+        };
+    }
+
+    #[func]
+    fn get_side_to_move(&self) -> String {
+        self.setup.side_to_move.name().into()
+    }
+
+    /// Takes the FEN castling field, e.g. `KQkq`, `Kq` or `-`
+    #[func]
+    fn set_castling_rights(&mut self, rights: String) {
+        self.setup.set_castling_rights(&rights);
+    }
+
+    #[func]
+    fn get_castling_rights(&self) -> String {
+        self.setup.get_castling_rights()
+    }
+
+    /// Takes a square name like `e3`, or an empty string to clear it. Returns false for malformed names.
+    #[func]
+    fn set_en_passant(&mut self, square: String) -> bool {
+        if square.is_empty() || square == "-" {
+            self.setup.en_passant = None;
+            return true;
+        }
+        match GodotSquare::from_name(&square) {
+            Some(en_passant) => {
+                self.setup.en_passant = Some(en_passant);
+                true
+            }
+            None => false,
+        }
+    }
+
+    #[func]
+    fn get_en_passant(&self) -> String {
+        self.setup.en_passant.map_or(String::new(), |square| square.to_string())
+    }
+
+    #[func]
+    fn get_fen(&self) -> String {
+        self.setup.get_fen()
+    }
+
+    /// Returns an empty string for a legal position, otherwise a message describing the first problem found
+    #[func]
+    fn validate(&self) -> String {
+        match self.find_problem() {
+            Some(problem) => problem,
+            None => String::new(),
+        }
+    }
+
+    fn find_problem(&self) -> Option<String> {
+        if let Some(problem) = self.setup.find_problem() {
+            return Some(problem);
+        }
+        // Anything the checks above miss is still caught by the engine
+        ChessEngine::from_fen(&self.setup.get_fen())
+            .err()
+            .map(|error| format!("Invalid position: {}", error))
+    }
+
+    fn init_palette(&mut self) {
+        let palette_x = self.square_size * 8.;
+        for (row, kind) in Self::PALETTE_KINDS.into_iter().enumerate() {
+            for (column, color) in [GodotPieceColor::White, GodotPieceColor::Black].into_iter().enumerate() {
+                let mut piece = GodotPiece::new_alloc();
+                piece.bind_mut().set_piece(kind, color, self.square_size);
+                piece.bind_mut().set_image();
+                piece.set_position(Vector2::new(
+                    palette_x + column as f32 * self.square_size,
+                    row as f32 * self.square_size,
+                ));
+                self.base_mut().add_child(&piece);
+            }
+        }
+
+        let mut eraser = Label::new_alloc();
+        eraser.set_text("Erase");
+        eraser.set_position(Vector2::new(palette_x, Self::PALETTE_KINDS.len() as f32 * self.square_size));
+        eraser.set_size(Vector2::new(self.square_size, self.square_size));
+        self.base_mut().add_child(&eraser);
+
+        let mut palette_cursor = self.palette_cursor.clone();
+        palette_cursor.set_size(Vector2::new(self.square_size, self.square_size));
+        self.base_mut().add_child(&palette_cursor);
+        self.select_brush(self.brush);
+    }
+
+    fn get_board_square(&self, position: Vector2) -> Option<GodotSquare> {
+        let board_size = self.square_size * 8.;
+        if position.x < 0. || position.y < 0. || position.x >= board_size || position.y >= board_size {
+            return None;
+        }
+        Some(GodotSquare::from_ui_vector2(position, self.square_size, &self.orientation))
+    }
+
+    /// Palette slot under `position`, `Some(None)` being the eraser
+    fn get_palette_entry(&self, position: Vector2) -> Option<Option<SetupPiece>> {
+        let column = ((position.x - self.square_size * 8.) / self.square_size).floor();
+        let row = (position.y / self.square_size).floor();
+        if !(0. ..2.).contains(&column) || row < 0. {
+            return None;
+        }
+
+        let color = if column == 0. {
+            GodotPieceColor::White
+        } else {
+            GodotPieceColor::Black
+        };
+        match Self::PALETTE_KINDS.get(row as usize) {
+            Some(kind) => Some(Some((color, *kind))),
+            None if row as usize == Self::PALETTE_KINDS.len() && column == 0. => Some(None),
+            None => None,
+        }
+    }
+
+    fn select_brush(&mut self, brush: Option<SetupPiece>) {
+        self.brush = brush;
+        let (column, row) = match brush {
+            Some((color, kind)) => (
+                if color == GodotPieceColor::White { 0 } else { 1 },
+                Self::PALETTE_KINDS
+                    .iter()
+                    .position(|palette_kind| *palette_kind == kind)
+                    .unwrap_or(0),
+            ),
+            None => (0, Self::PALETTE_KINDS.len()),
+        };
+        let position = Vector2::new(self.square_size * (8 + column) as f32, self.square_size * row as f32);
+        self.palette_cursor.set_position(position);
+        self.palette_cursor.set_visible(true);
+    }
+
+    fn start_drag(&mut self, position: Vector2) {
+        if let Some(entry) = self.get_palette_entry(position) {
+            self.select_brush(entry);
+            if let Some((color, kind)) = entry {
+                self.drag_origin = Some(DragOrigin::Palette);
+                self.create_drag_piece(color, kind, position);
+            }
+            return;
+        }
+
+        let Some(square) = self.get_board_square(position) else {
+            return;
+        };
+        match self.setup.get_piece(&square) {
+            Some((color, kind)) => {
+                self.drag_origin = Some(DragOrigin::Board(square));
+                self.create_drag_piece(color, kind, position);
+            }
+            None => self.paint(&square),
+        }
+    }
+
+    fn end_drag(&mut self, position: Vector2) {
+        let Some(drag_origin) = self.drag_origin.take() else {
+            return;
+        };
+        if let Some(drag_piece) = self.drag_piece.take() {
+            drag_piece.free();
+        }
+
+        let target = self.get_board_square(position);
+        match (drag_origin, target) {
+            // Released where it was picked up, so it was a click
+            (DragOrigin::Board(origin), Some(target)) if origin == target => self.paint(&target),
+            (DragOrigin::Board(origin), Some(target)) => {
+                self.setup.set_piece(&target, self.setup.get_piece(&origin));
+                self.setup.set_piece(&origin, None);
+                self.refresh_pieces();
+            }
+            // Dragged off the board
+            (DragOrigin::Board(origin), None) => {
+                self.setup.set_piece(&origin, None);
+                self.refresh_pieces();
+            }
+            (DragOrigin::Palette, Some(target)) => self.paint(&target),
+            (DragOrigin::Palette, None) => {}
+        }
+    }
+
+    fn paint(&mut self, square: &GodotSquare) {
+        self.setup.set_piece(square, self.brush);
+        self.refresh_pieces();
+    }
+
+    fn create_drag_piece(&mut self, color: GodotPieceColor, kind: GodotPieceKind, position: Vector2) {
+        let mut piece = GodotPiece::new_alloc();
+        piece.bind_mut().set_piece(kind, color, self.square_size);
+        piece.bind_mut().set_image();
+        piece.set_modulate(Color::from_rgba(1., 1., 1., 0.7));
+        piece.set_position(position - Vector2::new(self.square_size / 2., self.square_size / 2.));
+        self.base_mut().add_child(&piece);
+        self.drag_piece = Some(piece);
+    }
+
+    fn refresh_pieces(&mut self) {
+        for piece in self.piece_nodes.drain(..) {
+            piece.free();
+        }
+
+        for index in 0..64 {
+            let square = GodotSquare::from_engine_square(Square::new(index));
+            let Some((color, kind)) = self.setup.get_piece(&square) else {
+                continue;
+            };
+            let mut piece = GodotPiece::new_alloc();
+            piece.bind_mut().set_piece(kind, color, self.square_size);
+            piece.bind_mut().set_image();
+            piece.set_position(square.get_ui_vector2(self.square_size, &self.orientation));
+            self.base_mut().add_child(&piece);
+            self.piece_nodes.push(piece);
+        }
+    }
+}
@@ -1,47 +1,171 @@
 use std::collections::{HashMap, HashSet};
 use std::error::Error;
+use std::fmt::Display;
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
 
-use godot::global::godot_print;
 use rustier_chess::board::Board;
 use rustier_chess::bots::bot::Bot;
-use rustier_chess::moves::move_mask_gen::MoveGenMasks;
 use rustier_chess::types::piece::Pieces;
-use rustier_chess::utils::zobrist::ZobristHasher;
 
 use crate::chess_pieces::{GodotPieceColor, GodotPieceKind};
-use crate::consts::ENGINE_MOVE_FOLDER_PATH;
 use crate::moves::GodotMove;
 use crate::square::GodotSquare;
+use crate::tables::{self, EngineTables};
+
+/// Reports to the Godot console inside the game, and to stderr for the command line binaries
+#[cfg(feature = "godot")]
+macro_rules! report_error {
+    ($($arg:tt)*) => { godot::global::godot_error!($($arg)*) };
+}
+#[cfg(not(feature = "godot"))]
+macro_rules! report_error {
+    ($($arg:tt)*) => { eprintln!($($arg)*) };
+}
+
+pub struct SearchResult {
+    pub best_move: GodotMove,
+    /// Centipawns from the point of view of the side to move
+    pub score: i32,
+}
+
+impl SearchResult {
+    /// Score in pawns with its sign, e.g. `+0.35`, or the moves to mate, e.g. `M3`
+    pub fn format_score(&self) -> String {
+        Evaluation::from_score(self.score).to_string()
+    }
+}
+
+/// Score of mating on the next ply. The bot lowers it by one for every further ply, so the fastest mate scores highest.
+pub const MATE_SCORE: i32 = 100_000;
+/// Mates further away than this many plies are beyond any depth the bot searches
+const MAX_MATE_PLIES: i32 = 1_000;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Evaluation {
+    Centipawns(i32),
+    /// Moves until mate, negative when getting mated
+    MateIn(i32),
+    /// A won tablebase ending, negative when lost. Syzygy only knows the moves until the next capture or pawn move
+    /// on the way to the win, which is what this counts.
+    TablebaseWin(i32),
+    TablebaseDraw,
+}
+
+impl Evaluation {
+    pub fn from_score(score: i32) -> Self {
+        let plies = MATE_SCORE - score.abs();
+        if (0..=MAX_MATE_PLIES).contains(&plies) {
+            let moves = (plies + 2) / 2;
+            Self::MateIn(if score > 0 { moves } else { -moves })
+        } else {
+            Self::Centipawns(score)
+        }
+    }
+
+    /// The same evaluation from the other side's point of view
+    pub fn flipped(self) -> Self {
+        match self {
+            Self::Centipawns(centipawns) => Self::Centipawns(-centipawns),
+            Self::MateIn(moves) => Self::MateIn(-moves),
+            Self::TablebaseWin(moves) => Self::TablebaseWin(-moves),
+            Self::TablebaseDraw => Self::TablebaseDraw,
+        }
+    }
+
+    /// Centipawns limited to `cap` either way, with mates and tablebase wins counting as `cap`
+    pub fn get_capped_centipawns(&self, cap: i32) -> i32 {
+        match *self {
+            Self::Centipawns(centipawns) => centipawns.clamp(-cap, cap),
+            Self::MateIn(moves) | Self::TablebaseWin(moves) => cap * moves.signum(),
+            Self::TablebaseDraw => 0,
+        }
+    }
+
+    /// Share of the eval bar for the side the evaluation is from, between 0 and 1
+    pub fn get_bar_fraction(&self) -> f32 {
+        match *self {
+            Self::Centipawns(centipawns) => 1. / (1. + (-centipawns as f32 / 400.).exp()),
+            Self::MateIn(moves) | Self::TablebaseWin(moves) if moves > 0 => 1.,
+            Self::MateIn(_) | Self::TablebaseWin(_) => 0.,
+            Self::TablebaseDraw => 0.5,
+        }
+    }
+}
+
+impl Display for Evaluation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Centipawns(centipawns) => write!(f, "{:+.2}", *centipawns as f32 / 100.),
+            Self::MateIn(moves) if *moves > 0 => write!(f, "M{}", moves),
+            Self::MateIn(moves) => write!(f, "-M{}", -moves),
+            Self::TablebaseWin(moves) if *moves > 0 => write!(f, "Win in {}", moves),
+            Self::TablebaseWin(moves) => write!(f, "Loss in {}", -moves),
+            Self::TablebaseDraw => write!(f, "Draw"),
+        }
+    }
+}
+
+/// A search of a copy of the position on its own thread, so the game keeps running while it thinks.
+/// Dropping it abandons the result.
+pub struct BackgroundSearch {
+    receiver: Receiver<Option<SearchResult>>,
+    turn: GodotPieceColor,
+}
+
+impl BackgroundSearch {
+    /// `None` while the search is running. Then the result, or `None` inside when the position had no legal moves.
+    pub fn try_take(&self) -> Option<Option<SearchResult>> {
+        self.receiver.try_recv().ok()
+    }
+
+    /// Like `try_take`, with the evaluation from White's point of view
+    pub fn try_get_white_evaluation(&self) -> Option<Option<Evaluation>> {
+        let result = self.try_take()?;
+        Some(result.map(|result| {
+            let evaluation = Evaluation::from_score(result.score);
+            match self.turn {
+                GodotPieceColor::White => evaluation,
+                GodotPieceColor::Black => evaluation.flipped(),
+            }
+        }))
+    }
+}
 
 pub struct ChessEngine {
     pub board: Board,
     pub bot: Bot,
-    hasher: ZobristHasher,
-    move_gen_mask: MoveGenMasks,
+    /// Position before each played move together with the move, for undo and replaying the game
+    history: Vec<(Board, GodotMove)>,
+    /// Position after each move stepped back over with `go_to_ply`, the next one last
+    future: Vec<(Board, GodotMove)>,
+    tables: Arc<EngineTables>,
 }
 
-impl Default for ChessEngine {
-    fn default() -> Self {
-        let hasher = ZobristHasher::load();
+impl ChessEngine {
+    /// Fails with the reason the move tables can't be loaded, as there is no engine without them
+    pub fn new() -> Result<Self, String> {
+        let tables = tables::get_engine_tables()?;
 
-        Self {
-            board: Board::new(&hasher),
+        Ok(Self {
+            board: Board::new(&tables.hasher),
             bot: Bot::default(),
-            hasher,
+            history: Vec::new(),
+            future: Vec::new(),
             // legal_moves: HashSet::with_capacity(218),  // Max legal moves in a chess position
-            move_gen_mask: MoveGenMasks::load_from_path(ENGINE_MOVE_FOLDER_PATH),
-        }
+            tables,
+        })
     }
-}
 
-impl ChessEngine {
-    pub fn new() -> Self {
-        Self::default()
+    /// Describes why the move tables can't be loaded, if they can't
+    pub fn check_tables() -> Result<(), String> {
+        tables::get_engine_tables().map(|_| ())
     }
 
     pub fn get_legal_moves(&mut self) -> HashMap<GodotSquare, HashSet<GodotMove>> {
         let mut output: HashMap<GodotSquare, HashSet<GodotMove>> = HashMap::new();
-        for engine_move in self.board.get_legal_moves(&self.move_gen_mask, &self.hasher) {
+        for engine_move in self.board.get_legal_moves(&self.tables.move_gen_mask, &self.tables.hasher) {
             let from = GodotSquare::from_engine_square(engine_move.get_origin());
 
             match output.get_mut(&from) {
@@ -58,19 +182,145 @@ impl ChessEngine {
     }
 
     pub fn play_move(&mut self, legal_move: &GodotMove) {
-        self.board.make_move(&legal_move.get_engine_move(), &self.hasher);
-        godot_print!("{}", self.board);
+        self.future.clear();
+        self.history.push((self.board.clone(), *legal_move));
+        self.board.make_move(&legal_move.get_engine_move(), &self.tables.hasher);
+    }
+
+    /// Takes back the last played move. Returns false if there is nothing to undo.
+    pub fn undo_move(&mut self) -> bool {
+        self.future.clear();
+        match self.history.pop() {
+            Some((board, _)) => {
+                self.board = board;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Shows the position after `ply` moves without forgetting the moves after it. Returns false if the game
+    /// is shorter.
+    pub fn go_to_ply(&mut self, ply: usize) -> bool {
+        while self.history.len() > ply {
+            let Some((board, legal_move)) = self.history.pop() else {
+                break;
+            };
+            self.future.push((std::mem::replace(&mut self.board, board), legal_move));
+        }
+        while self.history.len() < ply {
+            let Some((board, legal_move)) = self.future.pop() else {
+                break;
+            };
+            self.history.push((std::mem::replace(&mut self.board, board), legal_move));
+        }
+        self.history.len() == ply
+    }
+
+    /// Moves played so far, including those after the position `go_to_ply` went back to
+    pub fn get_played_moves(&self) -> Vec<GodotMove> {
+        let played = self.history.iter().map(|(_, legal_move)| *legal_move);
+        let stepped_back = self.future.iter().rev().map(|(_, legal_move)| *legal_move);
+        played.chain(stepped_back).collect()
+    }
+
+    pub fn get_ply(&self) -> usize {
+        self.history.len()
+    }
+
+    /// A fresh engine at the position the game started from
+    pub fn get_start_position(&self) -> Self {
+        let board = self.history.first().map_or(&self.board, |(board, _)| board).clone();
+        self.with_board(board)
+    }
+
+    /// A fresh engine at the current position, without the moves that led to it
+    pub fn copy_position(&self) -> Self {
+        self.with_board(self.board.clone())
+    }
+
+    fn with_board(&self, board: Board) -> Self {
+        Self {
+            board,
+            bot: Bot::default(),
+            history: Vec::new(),
+            future: Vec::new(),
+            tables: self.tables.clone(),
+        }
+    }
+
+    /// Searches the current position with the bot. Returns `None` when there are no legal moves.
+    pub fn search(&mut self, depth: u8) -> Option<SearchResult> {
+        if self
+            .board
+            .get_legal_moves(&self.tables.move_gen_mask, &self.tables.hasher)
+            .is_empty()
+        {
+            return None;
+        }
+        self.bot.set_depth(depth);
+        let (best_move, score) = self
+            .bot
+            .find_best_move(&mut self.board, &self.tables.move_gen_mask, &self.tables.hasher);
+        Some(SearchResult {
+            best_move: GodotMove::from_engine_move(best_move),
+            score,
+        })
+    }
+
+    /// Searches a copy of the current position on another thread with a bot of its own
+    pub fn search_in_background(&self, depth: u8) -> BackgroundSearch {
+        let mut engine = self.copy_position();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            // Fails if the search was abandoned, in which case nobody wants the result
+            let _ = sender.send(engine.search(depth));
+        });
+        BackgroundSearch {
+            receiver,
+            turn: self.get_turn(),
+        }
+    }
+
+    /// Number of leaf nodes of the legal move tree `depth` plies deep, for validating move generation
+    pub fn perft(&mut self, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let moves = self.board.get_legal_moves(&self.tables.move_gen_mask, &self.tables.hasher);
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+
+        let mut nodes = 0;
+        for engine_move in moves {
+            let board_before_move = self.board.clone();
+            self.board.make_move(&engine_move, &self.tables.hasher);
+            nodes += self.perft(depth - 1);
+            self.board = board_before_move;
+        }
+        nodes
+    }
+
+    /// Finds the legal move matching a UCI string like `e2e4` or `e7e8q`
+    pub fn get_legal_move_from_uci(&mut self, uci: &str) -> Option<GodotMove> {
+        let origin = GodotSquare::from_name(uci.get(0..2)?)?;
+        self.get_legal_moves()
+            .remove(&origin)?
+            .into_iter()
+            .find(|legal_move| legal_move.to_uci() == uci)
     }
 
     pub fn from_fen(fen: &str) -> Result<Self, Box<dyn Error>> {
-        let hasher = ZobristHasher::load();
+        let tables = tables::get_engine_tables()?;
 
         Ok(Self {
-            board: Board::from_fen(fen, &hasher)?,
+            board: Board::from_fen(fen, &tables.hasher)?,
             bot: Bot::default(),
-            hasher,
+            history: Vec::new(),
+            future: Vec::new(),
             // legal_moves: HashSet::with_capacity(218),  // Max legal moves in a chess position
-            move_gen_mask: MoveGenMasks::load_from_path(ENGINE_MOVE_FOLDER_PATH),
+            tables,
         })
     }
 
@@ -78,27 +328,128 @@ impl ChessEngine {
         let mut output: Vec<(GodotSquare, GodotPieceColor, GodotPieceKind)> = Vec::with_capacity(32);
         for i in 0..64 {
             let square = GodotSquare::from_field_index(i, player_color);
-            if let Some(piece) = self.board.get_piece_on_square(&square.get_square()) {
-                let color = if piece.color == 0 {
-                    GodotPieceColor::White
-                } else {
-                    GodotPieceColor::Black
-                };
-                let piece_kind = match piece.piece {
-                    Pieces::QUEEN => GodotPieceKind::Queen,
-                    Pieces::ROOK => GodotPieceKind::Rook,
-                    Pieces::BISHOP => GodotPieceKind::Bishop,
-                    Pieces::KNIGHT => GodotPieceKind::Knight,
-                    Pieces::PAWN => GodotPieceKind::Pawn,
-                    Pieces::KING => GodotPieceKind::King,
-                    _ => panic!("Unexpected piece type"),
-                };
+            if let Some((color, piece_kind)) = self.get_piece_on_square(&square) {
                 output.push((square, color, piece_kind));
             }
         }
         output
     }
 
+    pub fn get_piece_on_square(&self, square: &GodotSquare) -> Option<(GodotPieceColor, GodotPieceKind)> {
+        let piece = self.board.get_piece_on_square(&square.get_square())?;
+        let color = if piece.color == 0 {
+            GodotPieceColor::White
+        } else {
+            GodotPieceColor::Black
+        };
+        let piece_kind = match piece.piece {
+            Pieces::QUEEN => GodotPieceKind::Queen,
+            Pieces::ROOK => GodotPieceKind::Rook,
+            Pieces::BISHOP => GodotPieceKind::Bishop,
+            Pieces::KNIGHT => GodotPieceKind::Knight,
+            Pieces::PAWN => GodotPieceKind::Pawn,
+            Pieces::KING => GodotPieceKind::King,
+            other => {
+                report_error!("Unexpected piece type {} on {}", other, square);
+                return None;
+            }
+        };
+        Some((color, piece_kind))
+    }
+
+    /// Whether `legal_move` takes a piece, including en passant
+    pub fn is_capture(&self, legal_move: &GodotMove) -> bool {
+        if self.get_piece_on_square(&legal_move.get_destination()).is_some() {
+            return true;
+        }
+        let is_pawn = matches!(
+            self.get_piece_on_square(&legal_move.get_origin()),
+            Some((_, GodotPieceKind::Pawn))
+        );
+        is_pawn && legal_move.get_origin().get_file() != legal_move.get_destination().get_file()
+    }
+
+    /// Kind of the piece `legal_move` takes, a pawn for en passant
+    pub fn get_captured_kind(&self, legal_move: &GodotMove) -> Option<GodotPieceKind> {
+        if !self.is_capture(legal_move) {
+            return None;
+        }
+        let captured = self.get_piece_on_square(&legal_move.get_destination());
+        Some(captured.map_or(GodotPieceKind::Pawn, |(_, kind)| kind))
+    }
+
+    /// Standard algebraic notation of a legal move in the current position, e.g. `Nbd7`, `exd6`, `O-O` or `e8=Q+`
+    pub fn get_san(&mut self, legal_move: &GodotMove) -> String {
+        let origin = legal_move.get_origin();
+        let destination = legal_move.get_destination();
+        let kind = self
+            .get_piece_on_square(&origin)
+            .map_or(GodotPieceKind::Pawn, |(_, kind)| kind);
+        let is_capture = self.is_capture(legal_move);
+
+        let mut output = if legal_move.is_castling() {
+            if destination.get_file() == 2 {
+                "O-O-O".to_string()
+            } else {
+                "O-O".to_string()
+            }
+        } else if kind == GodotPieceKind::Pawn {
+            let mut pawn_move = String::new();
+            if is_capture {
+                pawn_move.push((b'a' + origin.get_file()) as char);
+                pawn_move.push('x');
+            }
+            pawn_move.push_str(&destination.to_string());
+            if legal_move.is_promotion() {
+                pawn_move.push('=');
+                pawn_move.push(legal_move.to_uci().chars().last().unwrap_or('q').to_ascii_uppercase());
+            }
+            pawn_move
+        } else {
+            let mut piece_move = kind.to_string().to_uppercase();
+            // Other pieces of the same kind that can reach the same square
+            let rivals: Vec<GodotSquare> = self
+                .get_legal_moves()
+                .into_iter()
+                .filter(|(rival_origin, moves)| {
+                    *rival_origin != origin
+                        && moves.iter().any(|rival_move| rival_move.get_destination() == destination)
+                        && matches!(self.get_piece_on_square(rival_origin), Some((_, rival_kind)) if rival_kind == kind)
+                })
+                .map(|(rival_origin, _)| rival_origin)
+                .collect();
+            if !rivals.is_empty() {
+                let origin_name = origin.to_string();
+                if rivals.iter().all(|rival| rival.get_file() != origin.get_file()) {
+                    piece_move.push_str(&origin_name[..1]);
+                } else if rivals.iter().all(|rival| rival.get_rank() != origin.get_rank()) {
+                    piece_move.push_str(&origin_name[1..]);
+                } else {
+                    piece_move.push_str(&origin_name);
+                }
+            }
+            if is_capture {
+                piece_move.push('x');
+            }
+            piece_move.push_str(&destination.to_string());
+            piece_move
+        };
+
+        let mut board_after_move = self.board.clone();
+        board_after_move.make_move(&legal_move.get_engine_move(), &self.tables.hasher);
+        if board_after_move.is_in_check(&self.tables.move_gen_mask) {
+            let is_mate = board_after_move
+                .get_legal_moves(&self.tables.move_gen_mask, &self.tables.hasher)
+                .is_empty();
+            output.push(if is_mate { '#' } else { '+' });
+        }
+        output
+    }
+
+    pub fn is_in_check(&self) -> bool {
+        self.board.is_in_check(&self.tables.move_gen_mask)
+    }
+
     pub fn get_turn(&self) -> GodotPieceColor {
         if self.board.state.turn == 0 {
             GodotPieceColor::White
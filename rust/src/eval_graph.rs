@@ -0,0 +1,101 @@
+use godot::classes::{Control, IControl, InputEvent, InputEventMouseButton};
+use godot::global::MouseButton;
+use godot::prelude::*;
+
+use crate::consts::{EVAL_BAR_BLACK_COLOR, EVAL_BAR_WHITE_COLOR, SELECT_BORDER_COLOR};
+
+/// Evaluations beyond this many pawns either way are drawn at the edge
+const MAX_PAWNS: f32 = 10.;
+
+/// Evaluation over the course of a game. Clicking it picks the closest ply.
+#[derive(GodotClass)]
+#[class(init, base=Control)]
+pub struct EvalGraph {
+    /// White's evaluation in pawns, starting with the position before the first move
+    evaluations: Vec<f32>,
+    selected_ply: Option<usize>,
+    base: Base<Control>,
+}
+
+#[godot_api]
+impl IControl for EvalGraph {
+    fn draw(&mut self) {
+        let size = self.base().get_size();
+        let white = Color::from_html(EVAL_BAR_WHITE_COLOR).unwrap();
+        let black = Color::from_html(EVAL_BAR_BLACK_COLOR).unwrap();
+        let marker = Color::from_html(SELECT_BORDER_COLOR).unwrap();
+        let points = self.get_points();
+
+        self.base_mut().draw_rect(Rect2::new(Vector2::ZERO, size), black);
+        // White's share below the curve, like the eval bar
+        if points.len() > 1 {
+            let mut area = points.clone();
+            area.push(Vector2::new(points[points.len() - 1].x, size.y));
+            area.push(Vector2::new(points[0].x, size.y));
+            self.base_mut()
+                .draw_colored_polygon(&PackedVector2Array::from_iter(area), white);
+        }
+        self.base_mut()
+            .draw_line(Vector2::new(0., size.y / 2.), Vector2::new(size.x, size.y / 2.), Color::GRAY);
+
+        if let Some(point) = self.selected_ply.and_then(|ply| points.get(ply)) {
+            let point = *point;
+            self.base_mut()
+                .draw_line(Vector2::new(point.x, 0.), Vector2::new(point.x, size.y), marker);
+            self.base_mut().draw_circle(point, 4., marker);
+        }
+    }
+
+    fn gui_input(&mut self, event: Gd<InputEvent>) {
+        let Ok(mouse_button_event) = event.try_cast::<InputEventMouseButton>() else {
+            return;
+        };
+        if mouse_button_event.get_button_index() != MouseButton::LEFT || !mouse_button_event.is_pressed() {
+            return;
+        }
+        let x = mouse_button_event.get_position().x;
+        let closest_ply = self
+            .get_points()
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| (a.x - x).abs().total_cmp(&(b.x - x).abs()))
+            .map(|(ply, _)| ply);
+        if let Some(ply) = closest_ply {
+            self.select_ply(ply as i32);
+            self.base_mut().emit_signal("ply_clicked", &[(ply as i32).to_variant()]);
+        }
+    }
+}
+
+#[godot_api]
+impl EvalGraph {
+    /// Emitted with the number of moves played in the clicked position
+    #[signal]
+    fn ply_clicked(ply: i32);
+
+    #[func]
+    fn set_evaluations(&mut self, evaluations: PackedFloat32Array) {
+        self.evaluations = evaluations.to_vec();
+        self.selected_ply = None;
+        self.base_mut().queue_redraw();
+    }
+
+    #[func]
+    fn select_ply(&mut self, ply: i32) {
+        self.selected_ply = usize::try_from(ply).ok().filter(|ply| *ply < self.evaluations.len());
+        self.base_mut().queue_redraw();
+    }
+
+    fn get_points(&self) -> Vec<Vector2> {
+        let size = self.base().get_size();
+        let step = size.x / (self.evaluations.len().max(2) - 1) as f32;
+        self.evaluations
+            .iter()
+            .enumerate()
+            .map(|(ply, evaluation)| {
+                let share = evaluation.clamp(-MAX_PAWNS, MAX_PAWNS) / MAX_PAWNS;
+                Vector2::new(step * ply as f32, size.y / 2. * (1. - share))
+            })
+            .collect()
+    }
+}
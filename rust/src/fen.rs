@@ -0,0 +1,90 @@
+use crate::chess_pieces::{GodotPieceColor, GodotPieceKind};
+use crate::engine::ChessEngine;
+use crate::square::GodotSquare;
+
+pub const START_POSITION_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+/// Castling right with the squares its king and rook start on
+const CASTLING_SQUARES: [(char, [&str; 2]); 4] = [
+    ('K', ["e1", "h1"]),
+    ('Q', ["e1", "a1"]),
+    ('k', ["e8", "h8"]),
+    ('q', ["e8", "a8"]),
+];
+
+/// FEN of the position `engine` is at, `None` if it didn't start from `start_fen`. The engine doesn't write FEN, so
+/// castling rights, the en passant square and the move counters are worked out by replaying its moves.
+pub fn get_fen(start_fen: Option<&str>, engine: &ChessEngine) -> Option<String> {
+    let start_fen = start_fen.unwrap_or(START_POSITION_FEN);
+    let fields: Vec<&str> = start_fen.split_whitespace().collect();
+    let mut castling: String = fields.get(2).unwrap_or(&"-").replace('-', "");
+    let mut en_passant = fields.get(3).unwrap_or(&"-").to_string();
+    let mut halfmove_clock: u32 = fields.get(4).and_then(|field| field.parse().ok()).unwrap_or(0);
+    let mut fullmove_number: u32 = fields.get(5).and_then(|field| field.parse().ok()).unwrap_or(1);
+
+    let mut replay = ChessEngine::from_fen(start_fen).ok()?;
+    // Moves after a position picked with `go_to_ply` aren't on the board
+    for played_move in engine.get_played_moves().into_iter().take(engine.get_ply()) {
+        let origin = played_move.get_origin();
+        let destination = played_move.get_destination();
+        let is_pawn = matches!(replay.get_piece_on_square(&origin), Some((_, GodotPieceKind::Pawn)));
+        halfmove_clock = if is_pawn || replay.is_capture(&played_move) {
+            0
+        } else {
+            halfmove_clock + 1
+        };
+        // Moving the king or a rook, or losing a rook on its start square, gives up the right
+        let touched = [origin.to_string(), destination.to_string()];
+        castling.retain(|right| {
+            let squares = CASTLING_SQUARES
+                .iter()
+                .find(|(letter, _)| *letter == right)
+                .map_or([""; 2], |entry| entry.1);
+            !squares.iter().any(|square| touched.iter().any(|touched| touched == square))
+        });
+        en_passant = if is_pawn && origin.get_rank().abs_diff(destination.get_rank()) == 2 {
+            let rank = (origin.get_rank() + destination.get_rank()) / 2;
+            format!("{}{}", (b'a' + origin.get_file()) as char, rank + 1)
+        } else {
+            "-".to_string()
+        };
+        if replay.get_turn() == GodotPieceColor::Black {
+            fullmove_number += 1;
+        }
+        replay.play_move(&played_move);
+    }
+
+    let mut placement = String::with_capacity(72);
+    for row in 0..8 {
+        let mut n_empty = 0;
+        for column in 0..8 {
+            match engine.get_piece_on_square(&GodotSquare::from_u8(column, row, &GodotPieceColor::White)) {
+                Some((color, kind)) => {
+                    if n_empty > 0 {
+                        placement.push_str(&n_empty.to_string());
+                        n_empty = 0;
+                    }
+                    placement.push(kind.get_fen_char(color));
+                }
+                None => n_empty += 1,
+            }
+        }
+        if n_empty > 0 {
+            placement.push_str(&n_empty.to_string());
+        }
+        if row < 7 {
+            placement.push('/');
+        }
+    }
+    if castling.is_empty() {
+        castling.push('-');
+    }
+    Some(format!(
+        "{} {} {} {} {} {}",
+        placement,
+        engine.get_turn(),
+        castling,
+        en_passant,
+        halfmove_clock,
+        fullmove_number
+    ))
+}
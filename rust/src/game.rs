@@ -1,35 +1,104 @@
-use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
-use crate::chess_board::{GodotBoard, GodotSelectSquare, LegalMoveHelper, PromotionRect};
+use crate::accessibility::Announcer;
+use crate::analysis::{AnalysisJob, GameAnalysis};
+use crate::beginner::BeginnerBot;
+use crate::book::OpeningBook;
+use crate::chess_board::{EvalBar, GodotBoard, GodotSelectSquare, HintArrow, LegalMoveHelper, PromotionRect, free_if_orphaned};
 use crate::chess_pieces::{GodotPiece, GodotPieceColor, GodotPieceKind};
-use crate::consts::{CAPTURE_SOUND_FILE_NAME, MOVE_SOUND_FILE_NAME, RESOURCES_FOLDER_PATH, SOUNDS_SUBFOLDER_PATH};
-use crate::engine::ChessEngine;
+use crate::clock::{GameClock, TimeControl};
+use crate::consts::{
+    ANALYSIS_DEPTH, BASE_ANIMATION_DURATION, BOT_MOVE_DELAY_SECONDS, EVAL_BAR_WIDTH, EVAL_SEARCH_DEPTH, FOCUS_BORDER_COLOR,
+    GHOST_PIECE_ALPHA, HIGH_CONTRAST_FOCUS_BORDER_COLOR, HIGH_CONTRAST_HINT_ARROW_COLOR, HIGH_CONTRAST_LEGAL_MOVE_HELPER_COLOR,
+    HIGH_CONTRAST_SELECT_BORDER_COLOR, HINT_ARROW_COLOR, LEGAL_MOVE_HELPER_COLOR, LOW_TIME_THRESHOLD_SECONDS, MAX_BOT_LEVEL,
+    MIN_BOT_LEVEL, MULTI_PV_MAX_DEPTH, SELECT_BORDER_COLOR,
+};
+use crate::controller::{GameController, ViewCommand};
+use crate::engine::{BackgroundSearch, ChessEngine, Evaluation};
+use crate::fen;
+use crate::game_config::{GameConfig, OpponentKind};
 use crate::moves::GodotMove;
-use crate::sounds::GodotSounds;
+use crate::multi_pv::{MultiPvSearch, MultiPvUpdate, PvLine};
+use crate::opponent::{BookOptions, BuiltinBot, ClockState, MoveRequest, Opponent, OpponentEvent, UciOpponent};
+use crate::pgn::GameRecord;
+use crate::settings::GodotSettings;
+use crate::sounds::{SoundBank, SoundEvent};
 use crate::square::GodotSquare;
-use godot::classes::{INode2D, ITextureRect, InputEvent, InputEventMouseButton, Node2D};
-use godot::global::MouseButton;
+use crate::tablebase::{TablebaseProbe, Tablebases};
+use godot::classes::notify::Node2DNotification;
+use godot::classes::{
+    FileAccess, INode2D, ITextureRect, InputEvent, InputEventKey, InputEventMouseButton, Label, Node2D, ProjectSettings, Time,
+};
+use godot::global::{Key, MouseButton};
 use godot::prelude::*;
 use rustier_chess::types::square::Square;
 
 #[derive(GodotClass)]
 #[class(base=Node2D)]
 struct GodotGame {
+    /// Post-game analysis with the key it's cached under
+    analysis: Option<(String, GameAnalysis)>,
+    analysis_job: Option<(String, AnalysisJob)>,
+    /// Free play for both sides, where the multi-PV panel is available
+    analysis_mode: bool,
+    analysis_progress: usize,
+    animation_duration: f64,
+    announcer: Announcer,
     board_background: Gd<GodotBoard>,
-    engine: ChessEngine,
-    legal_moves: HashMap<GodotSquare, HashSet<GodotMove>>,
+    bot_color: Option<GodotPieceColor>,
+    /// Seconds left before the bot plays, so its reply doesn't land in the same frame as the player's move
+    bot_move_countdown: Option<f64>,
+    clock: Option<GameClock>,
+    clock_labels: Vec<(GodotPieceColor, Gd<Label>)>,
+    controller: GameController,
+    /// `None` until `new_game` has a position, which it can't without the engine tables
+    engine: Option<ChessEngine>,
+    eval_bar: Gd<EvalBar>,
+    /// Search for the eval bar of the current position, dropped when the position changes
+    eval_search: Option<BackgroundSearch>,
+    focus_cursor: Gd<GodotSelectSquare>,
+    focus_square: Option<GodotSquare>,
+    /// Pool of translucent pieces previewing a line, the first `ghost_piece_count` are showing
+    ghost_pieces: Vec<Gd<GodotPiece>>,
+    ghost_piece_count: usize,
+    high_contrast: bool,
+    /// The hint showing for the current position, if any
+    hint: Option<GodotMove>,
+    hint_arrow: Gd<HintArrow>,
+    hint_depth: u8,
+    hint_label: Gd<Label>,
+    /// `None` for unlimited hints
+    hint_limit: Option<u32>,
+    /// Search for a requested hint, dropped when the position changes
+    hint_search: Option<BackgroundSearch>,
+    hints_used: u32,
+    initialized: bool,
+    /// Pool of helper polygons, the first `legal_move_helper_count` are showing
     legal_move_helpers: Vec<Gd<LegalMoveHelper>>,
+    legal_move_helper_count: usize,
+    /// Made by the first multi-PV search, whose thread then searches every later position
+    multi_pv: Option<MultiPvSearch>,
+    /// 0 while the multi-PV panel is off
+    multi_pv_line_count: usize,
+    multi_pv_lines: Vec<PvLine>,
+    /// Plays `bot_color`, `None` when both sides are human
+    opponent: Option<Box<dyn Opponent>>,
+    /// Hidden piece nodes kept for reuse. Like every other node here they are children of the game, so they are
+    /// freed together with it.
+    piece_pool: Vec<Gd<GodotPiece>>,
     pieces: [Option<Gd<GodotPiece>>; 64],
     player_color: GodotPieceColor,
     promotion_rect: Gd<PromotionRect>,
-    promotion_square: Option<GodotSquare>,
+    rated: bool,
+    record: GameRecord,
     select_square: Gd<GodotSelectSquare>,
-    selected_piece_kind: Option<GodotPieceKind>,
-    selected_piece_square: Option<GodotSquare>,
-    sound_move: Gd<GodotSounds>,
-    sound_capture: Gd<GodotSounds>,
+    show_eval_bar: bool,
+    show_legal_moves: bool,
+    sounds: SoundBank,
     square_size: f32,
-    turn: GodotPieceColor,
+    tablebase_adjudication: bool,
+    /// `None` when no Syzygy folder is set up or it has no tables
+    tablebases: Option<Arc<Tablebases>>,
     base: Base<Node2D>,
 }
 
@@ -37,201 +106,135 @@ struct GodotGame {
 impl INode2D for GodotGame {
     fn init(base: Base<Node2D>) -> Self {
         Self {
+            analysis: None,
+            analysis_job: None,
+            analysis_mode: false,
+            analysis_progress: 0,
+            animation_duration: BASE_ANIMATION_DURATION,
+            announcer: Announcer::new(),
             board_background: GodotBoard::new_alloc(),
-            engine: ChessEngine::new(),
-            legal_moves: HashMap::with_capacity(16),
+            bot_color: None,
+            bot_move_countdown: None,
+            clock: None,
+            clock_labels: Vec::with_capacity(2),
+            controller: GameController::new(),
+            engine: None,
+            eval_bar: EvalBar::new_alloc(),
+            eval_search: None,
+            focus_cursor: GodotSelectSquare::new_alloc(),
+            focus_square: None,
+            ghost_pieces: Vec::new(),
+            ghost_piece_count: 0,
+            high_contrast: false,
+            hint: None,
+            hint_arrow: HintArrow::new_alloc(),
+            hint_depth: MIN_BOT_LEVEL as u8,
+            hint_label: Label::new_alloc(),
+            hint_limit: None,
+            hint_search: None,
+            hints_used: 0,
+            initialized: false,
             legal_move_helpers: Vec::with_capacity(20),
+            legal_move_helper_count: 0,
+            multi_pv: None,
+            multi_pv_line_count: 0,
+            multi_pv_lines: Vec::new(),
+            opponent: None,
+            piece_pool: Vec::with_capacity(8),
             pieces: [const { None }; 64],
             player_color: GodotPieceColor::White,
             promotion_rect: PromotionRect::new_alloc(),
-            promotion_square: None,
+            rated: false,
+            record: GameRecord::default(),
             select_square: GodotSelectSquare::new_alloc(),
-            selected_piece_kind: None,
-            selected_piece_square: None,
-            sound_move: GodotSounds::empty(),
-            sound_capture: GodotSounds::empty(),
+            show_eval_bar: true,
+            show_legal_moves: true,
+            sounds: SoundBank::new(),
             square_size: 100.,
-            turn: GodotPieceColor::White,
+            tablebase_adjudication: false,
+            tablebases: None,
             base,
         }
     }
 
-    fn input(&mut self, input_event: Gd<InputEvent>) {
-        if let Ok(mouse_button_event) = input_event.try_cast::<InputEventMouseButton>() {
-            if mouse_button_event.get_button_index() == MouseButton::LEFT && mouse_button_event.is_pressed() {
-                self.clear_helpers();
-                let click_position =
-                    GodotSquare::from_ui_vector2(mouse_button_event.get_position(), self.square_size, &self.player_color);
-
-                if let Some(promotion_square) = self.promotion_square {
-                    if click_position.get_file() != promotion_square.get_file()
-                        || (promotion_square.get_rank().abs_diff(click_position.get_rank()) > 3)
-                    {
-                        self.selected_piece_square = None;
-                        self.selected_piece_kind = None;
-                        self.hide_select_square();
-                        self.promotion_square = None;
-                        self.promotion_rect.hide();
+    /// The scene nodes are only added as children once a game starts
+    fn on_notification(&mut self, what: Node2DNotification) {
+        if what == Node2DNotification::PREDELETE {
+            free_if_orphaned(self.board_background.clone());
+            free_if_orphaned(self.eval_bar.clone());
+            free_if_orphaned(self.focus_cursor.clone());
+            free_if_orphaned(self.hint_arrow.clone());
+            free_if_orphaned(self.hint_label.clone());
+            free_if_orphaned(self.promotion_rect.clone());
+            free_if_orphaned(self.select_square.clone());
+        }
+    }
 
-                        return;
-                    }
+    fn process(&mut self, delta: f64) {
+        let finished_search = self.eval_search.as_ref().and_then(|search| search.try_get_white_evaluation());
+        if let Some(evaluation) = finished_search {
+            self.eval_search = None;
+            if let Some(evaluation) = evaluation {
+                self.show_evaluation(evaluation);
+            }
+        }
+        self.poll_analysis();
+        self.poll_hint();
+        let multi_pv_update = self.multi_pv.as_ref().and_then(|search| search.try_take_latest());
+        if let Some(update) = multi_pv_update {
+            self.show_multi_pv(update);
+        }
 
-                    let selected_piece_square = self.selected_piece_square.unwrap();
-
-                    let origin_index = selected_piece_square.get_field_index(&self.player_color);
-                    let destination_index = promotion_square.get_field_index(&self.player_color);
-
-                    match self.pieces[destination_index].clone() {
-                        // Capture
-                        Some(piece_in_field) => {
-                            self.base_mut().remove_child(&piece_in_field);
-                            self.play_capture_sound();
-                        }
-                        // Move
-                        None => {
-                            self.play_move_sound();
-                        }
-                    }
-                    let child_to_drop_origin = self.pieces[origin_index].clone().unwrap();
-                    self.base_mut().remove_child(&child_to_drop_origin);
-                    self.pieces[origin_index] = None;
-
-                    let (new_piece_kind, promotion_piece_n) = match promotion_square.get_rank().abs_diff(click_position.get_rank()) {
-                        0 => {
-                            (GodotPieceKind::Queen, 0)
-                        }
-                        1 => {
-                            (GodotPieceKind::Knight, 3)
-                        }
-                        2 => {
-                            (GodotPieceKind::Rook, 1)
-                        }
-                        3 => {
-                            (GodotPieceKind::Bishop, 2)
-                        }
-                        _ => panic!("Should be able to get here"),
-                    };
-
-                    self.init_piece(new_piece_kind, self.turn, &promotion_square);
-
-                    let legal_move = GodotMove::from_origin_destination_and_promotion(
-                        &selected_piece_square,
-                        &promotion_square,
-                        promotion_piece_n,
-                    );
+        if self.controller.is_game_over() {
+            return;
+        }
+        if let Some(countdown) = self.bot_move_countdown.as_mut() {
+            *countdown -= delta;
+            if *countdown <= 0. {
+                self.bot_move_countdown = None;
+                self.play_bot_move();
+            }
+        }
+        self.poll_opponent();
+        if self.controller.is_game_over() {
+            return;
+        }
 
-                    self.engine.play_move(&legal_move);
-                    self.end_turn();
-                    return;
-                }
+        let turn = self.controller.get_turn();
+        let Some(clock) = self.clock.as_mut() else {
+            return;
+        };
 
-                godot_print!("Turn {}", self.turn);
-                godot_print!("click position: {}", click_position);
-                godot_print!("Selected i: {:?}", self.selected_piece_square);
-
-                match self.selected_piece_square {
-                    None => {
-                        if let Some(piece) = self.pieces.get(click_position.get_field_index(&self.player_color)).unwrap() {
-                            if piece.bind().color == self.turn {
-                                self.selected_piece_kind = Some(piece.bind().kind);
-                                self.selected_piece_square = Some(click_position);
-                                // mark square
-                                self.move_select_square(&click_position);
-                                // get move helpers
-                                if self.legal_moves.is_empty() {
-                                    self.legal_moves = self.engine.get_legal_moves();
-                                }
-                                // create helpers
-                                if let Some(helpers) = self.legal_moves.get(&click_position) {
-                                    self.draw_helpers(helpers.clone());
-                                }
-                            }
-                        }
-                        return;
-                    }
-                    Some(selected_piece_square) => {
-                        match self.pieces.get(click_position.get_field_index(&self.player_color)).unwrap() {
-                            Some(piece_in_field) => {
-                                // Change selection if same color
-                                if piece_in_field.bind().color == self.turn {
-                                    self.move_select_square(&click_position);
-                                    self.selected_piece_square = Some(click_position);
-                                    if let Some(helpers) = self.legal_moves.get(&click_position) {
-                                        self.draw_helpers(helpers.clone());
-                                    }
-                                    return;
-                                }
-
-                                match self.get_legal_move_from_origin_and_destination(&selected_piece_square, &click_position) {
-                                    // Found move
-                                    Some(legal_move) => {
-                                        if legal_move.is_promotion() {
-                                            self.promotion_rect.bind_mut().show(&click_position, self.square_size);
-                                            self.promotion_square = Some(click_position);
-                                            return;
-                                        }
-
-                                        // capture
-                                        self.engine.play_move(&legal_move);
-                                        let child_to_drop = piece_in_field.clone();
-                                        self.base_mut().remove_child(&child_to_drop);
-                                        self.move_piece(&selected_piece_square, &click_position);
-                                        self.play_capture_sound();
-                                    }
-                                    // Did not find move
-                                    None => {
-                                        self.hide_select_square();
-                                        return;
-                                    }
-                                }
-                            }
-                            None => {
-                                // Move
-                                match self.get_legal_move_from_origin_and_destination(&selected_piece_square, &click_position) {
-                                    // Found move
-                                    Some(legal_move) => {
-                                        // is en passant capture
-                                        if let Some(en_passant_square) = self.engine.board.state.en_passant {
-                                            if GodotSquare::from_engine_square(en_passant_square) == click_position
-                                                && self.selected_piece_kind.unwrap() == GodotPieceKind::Pawn
-                                            {
-                                                let capture_square_index = if self.player_color == self.turn {
-                                                    click_position.get_field_index(&self.player_color) + 8
-                                                } else {
-                                                    click_position.get_field_index(&self.player_color) - 8
-                                                };
-                                                let child_to_drop = self.pieces[capture_square_index].clone().unwrap();
-                                                self.base_mut().remove_child(&child_to_drop);
-                                                self.pieces[capture_square_index] = None;
-                                            }
-                                        }
-
-                                        if legal_move.is_promotion() {
-                                            self.promotion_rect.bind_mut().show(&click_position, self.square_size);
-                                            self.promotion_square = Some(click_position);
-                                            return;
-                                        }
-
-                                        // castling
-                                        if legal_move.is_castling() {
-                                            self.move_rook_for_castling(&legal_move);
-                                        }
-
-                                        self.engine.play_move(&legal_move);
-                                        self.move_piece(&selected_piece_square, &click_position);
-                                        self.play_move_sound();
-                                    }
-                                    // Did not find move
-                                    None => {
-                                        self.hide_select_square();
-                                        return;
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-                self.end_turn();
+        let flagged = clock.tick(&turn, delta);
+        let low_time = clock.take_low_time_warning(&turn, LOW_TIME_THRESHOLD_SECONDS);
+        self.update_clock_labels();
+        if flagged {
+            self.finish_game(turn.opponent_turn().get_win_result(), "timeout");
+        } else if low_time {
+            self.sounds.play(SoundEvent::LowTime);
+        }
+    }
+
+    fn input(&mut self, input_event: Gd<InputEvent>) {
+        if self.controller.is_game_over() || !self.is_human_turn() {
+            return;
+        }
+
+        if let Ok(mouse_button_event) = input_event.clone().try_cast::<InputEventMouseButton>() {
+            let position = mouse_button_event.get_position();
+            let board_size = self.square_size * 8.;
+            // The window is wider than the board, so clicks can land on the side panel
+            let on_board = position.x >= 0. && position.y >= 0. && position.x < board_size && position.y < board_size;
+            if mouse_button_event.get_button_index() == MouseButton::LEFT && mouse_button_event.is_pressed() && on_board {
+                let click_position = GodotSquare::from_ui_vector2(position, self.square_size, &self.player_color);
+                self.select_board_square(click_position);
+            }
+            return;
+        }
+
+        if let Ok(key_event) = input_event.try_cast::<InputEventKey>() {
+            if key_event.is_pressed() {
+                self.handle_key(key_event.get_keycode());
             }
         }
     }
@@ -239,60 +242,966 @@ impl INode2D for GodotGame {
 
 #[godot_api]
 impl GodotGame {
+    #[signal]
+    fn move_played(san: GString, uci: GString, is_capture: bool, is_check: bool);
+
+    /// Color is `white` or `black`
+    #[signal]
+    fn turn_changed(color: GString);
+
+    #[signal]
+    fn promotion_requested(square: GString);
+
+    /// Emitted with the color of the side in check
+    #[signal]
+    fn check(color: GString);
+
+    /// Result is `1-0`, `0-1` or `1/2-1/2`, reason is `checkmate`, `stalemate` or `timeout`
+    #[signal]
+    fn game_over(result: GString, reason: GString);
+
+    /// The selected piece was sent onto a piece it can't take. Clicking an empty square only drops the selection.
+    #[signal]
+    fn illegal_move_attempted(from: GString, to: GString);
+
+    /// Score is in pawns from the point of view of the side to move, e.g. `+0.35`
+    #[signal]
+    fn hint_shown(san: GString, uci: GString, score: GString);
+
+    /// Emitted with the number of positions searched so far and in total
+    #[signal]
+    fn analysis_progress(done: i32, total: i32);
+
+    #[signal]
+    fn analysis_finished();
+
+    /// Each line is a dictionary with its `eval` from White's point of view, e.g. `+0.35` or `-M2`, its `depth`,
+    /// and its moves as `san` and `uci` arrays. Depths count the plies searched, the root move included.
+    #[signal]
+    fn multi_pv_updated(lines: Array<Dictionary>, depth: i32, nodes: i64, nodes_per_second: i64, elapsed_ms: i64);
+
+    /// Emitted when the pieces on screen got out of sync with the engine and were rebuilt from it
+    #[signal]
+    fn board_resynced(reason: GString);
+
+    /// Progress of the computer opponent's search, e.g. the depth and best line of a UCI engine
+    #[signal]
+    fn opponent_info(text: GString);
+
+    /// Emitted when the computer opponent crashes, stops answering or plays an illegal move. The game is then over.
+    #[signal]
+    fn opponent_failed(reason: GString);
+
+    /// Emitted after the bot played a move straight from its opening book
+    #[signal]
+    fn book_move_played(uci: GString);
+
+    /// Applies the presentation settings to a running game. Time control is only read when the game starts.
     #[func]
-    fn custom_ready(&mut self) {
-        self.init_board();
-        self.init_select_square();
-        self.init_pieces();
-        self.init_promotion_rect();
-        self.init_sounds();
+    fn apply_settings(&mut self, settings: Gd<GodotSettings>) {
+        let settings = settings.bind();
+        self.board_background.bind_mut().set_theme(&settings.theme.to_string());
+        self.sounds
+            .set_volume(if settings.muted { 0. } else { settings.sound_volume });
+        self.animation_duration = if settings.animation_speed > 0. {
+            BASE_ANIMATION_DURATION / settings.animation_speed as f64
+        } else {
+            0.
+        };
+        self.show_legal_moves = settings.show_legal_moves;
+        self.controller.set_auto_queen(settings.auto_queen);
+        let show_eval_bar = settings.show_eval_bar;
+        drop(settings);
+
+        if !self.show_legal_moves {
+            self.clear_helpers();
+        }
+        if show_eval_bar != self.show_eval_bar {
+            self.show_eval_bar = show_eval_bar;
+            self.update_eval_bar();
+        }
     }
 
+    /// Plays a move given in UCI notation, e.g. `e2e4` or `e7e8q`, like a move made on the board.
+    /// Returns false if the move isn't legal or the game is over.
     #[func]
-    fn start(&mut self, color: String) {
-        self.player_color = if color == "white" {
-            GodotPieceColor::White
-        } else {
-            GodotPieceColor::Black
+    fn make_move(&mut self, uci: String) -> bool {
+        if self.controller.is_game_over() {
+            return false;
+        }
+        let Some(legal_move) = self.engine.as_ref().and_then(|engine| engine.get_legal_move_from_uci(&uci)) else {
+            return false;
+        };
+
+        let commands = self.controller.play_move(legal_move);
+        if commands.is_empty() {
+            return false;
+        }
+        self.render(commands);
+        true
+    }
+
+    /// Maps each square name with a movable piece to the UCI strings of its legal moves
+    #[func]
+    fn get_legal_moves(&mut self) -> Dictionary {
+        let mut output = Dictionary::new();
+        let Some(engine) = self.engine.as_mut().filter(|_| !self.controller.is_game_over()) else {
+            return output;
         };
-        self.custom_ready();
+        for (origin, moves) in engine.get_legal_moves() {
+            let mut uci_moves: Vec<String> = moves.iter().map(|legal_move| legal_move.to_uci()).collect();
+            uci_moves.sort();
+            let uci_moves: PackedStringArray = uci_moves.iter().map(|uci| GString::from(uci.as_str())).collect();
+            output.set(origin.to_string(), uci_moves);
+        }
+        output
+    }
+
+    /// FEN letter of the piece on `square`, upper case for white, or an empty string
+    #[func]
+    fn get_piece_at(&self, square: String) -> String {
+        GodotSquare::from_name(&square)
+            .and_then(|square| self.get_piece_on_square(&square))
+            .map_or(String::new(), |(color, kind)| kind.get_fen_char(color).to_string())
     }
 
+    /// 64 FEN letters or empty strings, ordered a1, b1, ..., h8
     #[func]
-    fn start_from_fen(&mut self, fen: String) {
-        if let Ok(engine) = ChessEngine::from_fen(&fen) {
-            self.engine = engine;
-            self.player_color = self.engine.get_turn();
-            self.turn = self.player_color;
-            self.custom_ready();
+    fn get_board_array(&self) -> PackedStringArray {
+        (0..64)
+            .map(|index| {
+                let square = GodotSquare::from_engine_square(Square::new(index));
+                self.get_piece_on_square(&square).map_or(GString::new(), |(color, kind)| {
+                    GString::from(kind.get_fen_char(color).to_string())
+                })
+            })
+            .collect()
+    }
+
+    #[func]
+    fn get_turn(&self) -> String {
+        self.controller.get_turn().name().into()
+    }
+
+    #[func]
+    fn is_game_over(&self) -> bool {
+        self.controller.is_game_over()
+    }
+
+    /// Searches the bot's choice for the side to move in the background, then shows it as an arrow, notes it in the
+    /// PGN and emits `hint_shown`. Asking again in the same position doesn't use up another hint. Returns false if no
+    /// hint is available.
+    #[func]
+    fn request_hint(&mut self) -> bool {
+        if self.hint.is_some() || self.hint_search.is_some() {
+            return true;
         }
+        if self.controller.is_game_over() || !self.is_human_turn() || self.get_hints_left() == 0 {
+            return false;
+        }
+        let Some(engine) = self.engine.as_ref() else {
+            return false;
+        };
+        self.hint_search = Some(engine.search_in_background(self.hint_depth));
+        true
     }
 
+    /// Whether a requested hint is still being searched
     #[func]
-    fn check_fen_string(&mut self, fen: String) -> bool {
-        ChessEngine::from_fen(&fen).is_ok()
+    fn is_hint_pending(&self) -> bool {
+        self.hint_search.is_some()
+    }
+
+    /// Hints left in this game, -1 when they are unlimited
+    #[func]
+    fn get_hints_left(&self) -> i32 {
+        match self.hint_limit {
+            Some(limit) => limit.saturating_sub(self.hints_used) as i32,
+            None => -1,
+        }
+    }
+
+    /// The game so far, with the hints used as comments
+    #[func]
+    fn get_pgn(&self) -> GString {
+        let date = Time::singleton().get_date_string_from_system().to_string().replace('-', ".");
+        let tags = [
+            ("Event", "Casual game".to_string()),
+            ("Site", "?".to_string()),
+            ("Date", date),
+            ("Round", "-".to_string()),
+            ("White", self.get_player_name(GodotPieceColor::White)),
+            ("Black", self.get_player_name(GodotPieceColor::Black)),
+        ];
+        self.record.to_pgn(&tags).into()
+    }
+
+    /// Analyses every move of the finished game in the background, or loads the analysis from the cache.
+    /// `analysis_finished` is emitted once it's available. Returns false if the game isn't over.
+    #[func]
+    fn analyse_game(&mut self) -> bool {
+        let Some(engine) = self.engine.as_ref().filter(|_| self.controller.is_game_over()) else {
+            return false;
+        };
+        let moves = engine.get_played_moves();
+        let key = GameAnalysis::get_cache_key(self.record.get_start_fen(), &moves);
+        if self.analysis_job.as_ref().is_some_and(|(job_key, _)| *job_key == key) {
+            return true;
+        }
+        if !self.analysis.as_ref().is_some_and(|(analysis_key, _)| *analysis_key == key) {
+            match GameAnalysis::load(&key, ANALYSIS_DEPTH) {
+                Some(analysis) => self.analysis = Some((key, analysis)),
+                None => {
+                    let start_fen = self.record.get_start_fen().map(str::to_string);
+                    let job = AnalysisJob::start(
+                        engine.get_start_position(),
+                        start_fen,
+                        moves,
+                        ANALYSIS_DEPTH,
+                        self.tablebases.clone(),
+                    );
+                    self.analysis_progress = 0;
+                    self.analysis_job = Some((key, job));
+                    return true;
+                }
+            }
+        }
+        self.base_mut().emit_signal("analysis_finished", &[]);
+        true
+    }
+
+    /// One dictionary per move with its `san`, `classification` (`best`, `good`, `inaccuracy`, `mistake` or
+    /// `blunder`), the `better_move` in SAN or an empty string, `white_eval` in pawns after the move, and the
+    /// `tablebase` result for White like `Win in 12`, or an empty string outside the tablebases
+    #[func]
+    fn get_analysis(&self) -> Array<Dictionary> {
+        let Some((_, analysis)) = self.analysis.as_ref() else {
+            return Array::new();
+        };
+        analysis
+            .plies
+            .iter()
+            .map(|ply| {
+                let mut output = Dictionary::new();
+                output.set("san", ply.san.as_str());
+                output.set("classification", ply.classification.name());
+                output.set("better_move", ply.better_move.as_deref().unwrap_or(""));
+                output.set("white_eval", ply.white_eval as f32 / 100.);
+                let tablebase = ply.white_tablebase.map(|evaluation| evaluation.to_string());
+                output.set("tablebase", tablebase.unwrap_or_default());
+                output
+            })
+            .collect()
+    }
+
+    /// White's evaluation in pawns before the first move and after every move, for `EvalGraph`
+    #[func]
+    fn get_analysis_evaluations(&self) -> PackedFloat32Array {
+        let Some((_, analysis)) = self.analysis.as_ref() else {
+            return PackedFloat32Array::new();
+        };
+        std::iter::once(analysis.start_eval)
+            .chain(analysis.plies.iter().map(|ply| ply.white_eval))
+            .map(|white_eval| white_eval as f32 / 100.)
+            .collect()
+    }
+
+    /// Shows the position after `ply` moves of a finished game. Returns false if the game is still running or
+    /// shorter than that.
+    #[func]
+    fn show_ply(&mut self, ply: i32) -> bool {
+        if !self.controller.is_game_over() {
+            return false;
+        }
+        let Ok(ply) = usize::try_from(ply) else {
+            return false;
+        };
+        if !self.engine.as_mut().is_some_and(|engine| engine.go_to_ply(ply)) {
+            return false;
+        }
+        self.clear_hint();
+        self.clear_helpers();
+        self.hide_select_square();
+        self.sync_pieces();
+
+        let white_evaluation = self
+            .analysis
+            .as_ref()
+            .and_then(|(_, analysis)| analysis.get_white_evaluation(ply));
+        if let Some(white_evaluation) = white_evaluation {
+            self.eval_search = None;
+            self.show_evaluation(white_evaluation);
+        }
+        true
+    }
+
+    /// Keeps searching the best `line_count` lines of the current position in analysis games, reported through
+    /// `multi_pv_updated` as the search deepens. Returns false outside analysis games.
+    #[func]
+    fn start_multi_pv(&mut self, line_count: i32) -> bool {
+        if !self.analysis_mode || line_count <= 0 {
+            return false;
+        }
+        self.multi_pv_line_count = line_count as usize;
+        self.restart_multi_pv();
+        true
+    }
+
+    #[func]
+    fn stop_multi_pv(&mut self) {
+        self.multi_pv_line_count = 0;
+        self.restart_multi_pv();
+    }
+
+    /// Shows the position at the end of a multi-PV line as ghost pieces over the board
+    #[func]
+    fn preview_line(&mut self, index: i32) {
+        self.clear_line_preview();
+        let Some(line) = usize::try_from(index).ok().and_then(|index| self.multi_pv_lines.get(index)) else {
+            return;
+        };
+        let Some(engine) = self.engine.as_ref() else {
+            return;
+        };
+        let mut preview = engine.copy_position();
+        for line_move in line.moves.iter() {
+            preview.play_move(line_move);
+        }
+        let ghost_pieces: Vec<(GodotSquare, GodotPieceColor, GodotPieceKind)> = preview
+            .get_pieces_per_square(&self.player_color)
+            .into_iter()
+            .filter(|(square, color, kind)| engine.get_piece_on_square(square) != Some((*color, *kind)))
+            .collect();
+        for (square, color, kind) in ghost_pieces {
+            self.show_ghost_piece(kind, color, &square);
+        }
+    }
+
+    #[func]
+    fn clear_line_preview(&mut self) {
+        for ghost_piece in self.ghost_pieces[..self.ghost_piece_count].iter_mut() {
+            ghost_piece.set_visible(false);
+        }
+        self.ghost_piece_count = 0;
+    }
+
+    /// Plays the first move of a multi-PV line. Returns false if there is no such line.
+    #[func]
+    fn play_line(&mut self, index: i32) -> bool {
+        let first_move = usize::try_from(index)
+            .ok()
+            .and_then(|index| self.multi_pv_lines.get(index))
+            .map(|line| line.moves[0].to_uci());
+        match first_move {
+            Some(uci) => self.make_move(uci),
+            None => false,
+        }
+    }
+
+    /// Takes back moves until it's a player's turn again, so against a bot both its reply and the player's move. Returns
+    /// false if no move has been played.
+    #[func]
+    fn undo(&mut self) -> bool {
+        let Some(engine) = self.engine.as_mut() else {
+            return false;
+        };
+        // Undo applies to the end of the game, not to a position picked from the analysis
+        let ply_count = engine.get_played_moves().len();
+        engine.go_to_ply(ply_count);
+        let mut taken_back = 0;
+        // Stopping on the bot's turn would only have it play the same move again
+        while (taken_back == 0 || self.bot_color == Some(engine.get_turn())) && engine.undo_move() {
+            self.record.pop_move();
+            taken_back += 1;
+        }
+        if taken_back == 0 {
+            return false;
+        }
+        self.analysis = None;
+        self.analysis_job = None;
+        if let Some(opponent) = self.opponent.as_mut() {
+            opponent.cancel();
+        }
+        self.controller.set_game_over(false);
+        self.reset_board_state();
+        let turn = self.controller.get_turn();
+        self.base_mut().emit_signal("turn_changed", &[turn.name().to_variant()]);
+        self.bot_move_countdown = None;
+        self.schedule_bot_move();
+        true
+    }
+
+    /// Starts a game from `config`. The first call builds the board, later calls start over on the same node.
+    /// Returns an empty string on success, otherwise what's wrong with the config, in which case the current game is kept.
+    #[func]
+    fn new_game(&mut self, config: Gd<GameConfig>) -> GString {
+        let settings = GodotSettings::load_settings();
+        let default_time_control = settings.bind().time_control.to_string();
+        let resolved = match config.bind().resolve(&default_time_control) {
+            Ok(resolved) => resolved,
+            Err(error) => {
+                godot_error!("Invalid game config: {}", error);
+                return error.into();
+            }
+        };
+
+        let tablebases = Self::load_tablebases(&settings.bind());
+        // The only step that can still fail, so it comes before anything about the current game changes
+        let opponent: Option<Box<dyn Opponent>> = match &resolved.opponent {
+            OpponentKind::Human => None,
+            OpponentKind::Bot(level) => {
+                let mut bot = BuiltinBot::new(*level);
+                if let Some((book, options)) = Self::load_opening_book(&settings.bind()) {
+                    bot = bot.with_book(book, options);
+                }
+                if let Some(tablebases) = tablebases.clone() {
+                    bot = bot.with_tablebases(tablebases);
+                }
+                Some(Box::new(bot))
+            }
+            OpponentKind::Beginner(personality) => Some(Box::new(BeginnerBot::new(*personality))),
+            OpponentKind::Uci(path) => match UciOpponent::launch(path) {
+                Ok(opponent) => Some(Box::new(opponent)),
+                Err(error) => {
+                    godot_error!("Failed to start the UCI engine: {}", error);
+                    return error.into();
+                }
+            },
+        };
+
+        self.engine = Some(resolved.engine);
+        self.analysis_mode = resolved.analysis;
+        self.multi_pv_line_count = 0;
+        self.analysis = None;
+        self.analysis_job = None;
+        self.record = GameRecord::new(resolved.fen.as_deref());
+        self.player_color = resolved.orientation;
+        self.bot_color = opponent.is_some().then(|| resolved.side.opponent_turn());
+        self.opponent = opponent;
+        self.tablebases = tablebases;
+        self.tablebase_adjudication = settings.bind().tablebase_adjudication && !resolved.analysis;
+        self.bot_move_countdown = None;
+        self.controller.set_game_over(false);
+        self.focus_square = None;
+
+        // Like the time control, the hint limit is fixed for the whole game
+        let settings_bot_level = settings.bind().bot_level.clamp(MIN_BOT_LEVEL, MAX_BOT_LEVEL) as u8;
+        self.hint_depth = match resolved.opponent {
+            OpponentKind::Bot(level) => level,
+            OpponentKind::Human | OpponentKind::Uci(_) | OpponentKind::Beginner(_) => settings_bot_level,
+        };
+        let hint_limit = settings.bind().hint_limit;
+        self.hint_limit = if resolved.rated {
+            Some(0)
+        } else {
+            (!resolved.analysis && hint_limit >= 0).then_some(hint_limit as u32)
+        };
+        self.hints_used = 0;
+        self.rated = resolved.rated;
+
+        if self.initialized {
+            self.focus_cursor.set_visible(false);
+        } else {
+            self.init_board();
+            self.init_select_square();
+            self.init_focus_cursor();
+            self.init_promotion_rect();
+            self.init_hint();
+            self.init_eval_bar();
+            self.init_sounds();
+            self.apply_settings(settings);
+            self.initialized = true;
+        }
+        let eval_bar_shown = self.is_eval_bar_shown();
+        self.eval_bar.set_visible(eval_bar_shown);
+        self.eval_bar.bind_mut().set_orientation(&self.player_color);
+        self.eval_bar.bind_mut().show_evaluation(0.5, "");
+        self.reset_board_state();
+
+        for (_, label) in self.clock_labels.drain(..) {
+            label.free();
+        }
+        self.clock = None;
+        self.init_clock(resolved.time_control);
+        self.sounds.play(SoundEvent::GameStart);
+        self.schedule_bot_move();
+        GString::new()
+    }
+
+    /// Enables spoken announcements and the high-contrast marker set. Keyboard navigation works either way.
+    #[func]
+    fn set_accessibility_mode(&mut self, enabled: bool) {
+        self.announcer.set_enabled(enabled);
+        self.high_contrast = enabled;
+        self.apply_marker_colors();
+    }
+
+    #[func]
+    fn describe_position(&mut self) {
+        let Some(engine) = self.engine.as_ref() else {
+            return;
+        };
+        let pieces = engine.get_pieces_per_square(&self.player_color);
+        self.announcer
+            .speak(&Announcer::describe_position(&pieces, self.controller.get_turn()));
+    }
+
+    fn handle_key(&mut self, key: Key) {
+        if self.controller.get_pending_promotion().is_some() {
+            self.handle_promotion_key(key);
+            return;
+        }
+
+        match key {
+            Key::LEFT => self.move_focus(-1, 0),
+            Key::RIGHT => self.move_focus(1, 0),
+            Key::UP => self.move_focus(0, -1),
+            Key::DOWN => self.move_focus(0, 1),
+            Key::ENTER | Key::KP_ENTER | Key::SPACE => {
+                if let Some(focus_square) = self.focus_square {
+                    self.select_board_square(focus_square);
+                }
+            }
+            Key::D => self.describe_position(),
+            Key::ESCAPE => {
+                let commands = self.controller.deselect();
+                self.render(commands);
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_promotion_key(&mut self, key: Key) {
+        // Up and down follow the screen, the picker's index grows away from the promotion square
+        let away_from_square = if self.promotion_rect.bind().opens_upward() { -1 } else { 1 };
+        match key {
+            Key::UP => self
+                .promotion_rect
+                .bind_mut()
+                .move_highlight(-away_from_square, self.square_size),
+            Key::DOWN => self
+                .promotion_rect
+                .bind_mut()
+                .move_highlight(away_from_square, self.square_size),
+            Key::ENTER | Key::KP_ENTER | Key::SPACE => {
+                let index = self.promotion_rect.bind().get_highlighted_index();
+                self.choose_promotion(PromotionRect::PROMOTION_PIECES[index]);
+            }
+            Key::Q => self.choose_promotion(GodotPieceKind::Queen),
+            Key::N => self.choose_promotion(GodotPieceKind::Knight),
+            Key::R => self.choose_promotion(GodotPieceKind::Rook),
+            Key::B => self.choose_promotion(GodotPieceKind::Bishop),
+            Key::ESCAPE => {
+                let commands = self.controller.cancel_promotion();
+                self.render(commands);
+            }
+            _ => return,
+        }
+        if self.controller.get_pending_promotion().is_some() {
+            let index = self.promotion_rect.bind().get_highlighted_index();
+            self.announcer.speak(PromotionRect::PROMOTION_PIECES[index].name());
+        }
+    }
+
+    /// Moves the focus cursor in screen space, so arrow keys follow the board orientation
+    fn move_focus(&mut self, dx: i16, dy: i16) {
+        let focus_square = match self.focus_square {
+            Some(square) => {
+                let field_index = square.get_field_index(&self.player_color) as i16;
+                let x = (field_index % 8 + dx).clamp(0, 7);
+                let y = (field_index / 8 + dy).clamp(0, 7);
+                GodotSquare::from_i16(x, y, &self.player_color)
+            }
+            // The first key press lands on the square in front of the player's king
+            None => GodotSquare::from_i16(4, 6, &self.player_color),
+        };
+        self.focus_square = Some(focus_square);
+
+        self.focus_cursor
+            .set_position(focus_square.get_ui_vector2(self.square_size, &self.player_color));
+        self.focus_cursor.set_visible(true);
+
+        let piece = self.pieces[focus_square.get_field_index(&self.player_color)]
+            .as_ref()
+            .map(|piece| (piece.bind().color, piece.bind().kind));
+        self.announcer.speak(&Announcer::describe_square(&focus_square, piece));
+    }
+
+    fn announce_selection(&self, kind: GodotPieceKind, square: &GodotSquare, n_moves: usize) {
+        if !self.announcer.is_enabled() {
+            return;
+        }
+        self.announcer.speak(&format!(
+            "{} on {} selected, {} legal moves",
+            Announcer::describe_piece(self.controller.get_turn(), kind),
+            square,
+            n_moves
+        ));
+    }
+
+    /// Plays a legal move on the engine and brings the pieces on screen in line with it
+    fn play_engine_move(&mut self, legal_move: &GodotMove) {
+        let Some(engine) = self.engine.as_mut() else {
+            return;
+        };
+        let Some((_, kind)) = engine.get_piece_on_square(&legal_move.get_origin()) else {
+            self.resync_board(&format!("no piece to move on {}", legal_move.get_origin()));
+            return;
+        };
+        let captured = engine.get_captured_kind(legal_move);
+        let san = engine.get_san(legal_move);
+        engine.play_move(legal_move);
+        let is_check = engine.is_in_check();
+        self.record.push_move(&san);
+        self.clear_hint();
+
+        self.sync_pieces();
+        self.play_move_sounds(legal_move, captured.is_some());
+        self.announce_move(kind, &legal_move.get_origin(), &legal_move.get_destination(), captured);
+        self.base_mut().emit_signal(
+            "move_played",
+            &[
+                san.to_variant(),
+                legal_move.to_uci().to_variant(),
+                captured.is_some().to_variant(),
+                is_check.to_variant(),
+            ],
+        );
+        self.end_turn();
+    }
+
+    fn announce_move(&self, kind: GodotPieceKind, from: &GodotSquare, to: &GodotSquare, captured: Option<GodotPieceKind>) {
+        if !self.announcer.is_enabled() {
+            return;
+        }
+        let is_check = self.is_in_check();
+        self.announcer.speak(&Announcer::describe_move(
+            self.controller.get_turn(),
+            kind,
+            from,
+            to,
+            captured,
+            is_check,
+        ));
+    }
+
+    fn select_board_square(&mut self, click_position: GodotSquare) {
+        let commands = if self.controller.get_pending_promotion().is_some() {
+            let clicked_index = self
+                .promotion_rect
+                .bind()
+                .get_clicked_index(&click_position, &self.player_color);
+            match clicked_index {
+                Some(index) => self.controller.choose_promotion(PromotionRect::PROMOTION_PIECES[index]),
+                None => self.controller.cancel_promotion(),
+            }
+        } else {
+            let piece_color = self.get_piece_on_square(&click_position).map(|(color, _)| color);
+            self.controller.click(click_position, piece_color)
+        };
+        self.render(commands);
+    }
+
+    fn choose_promotion(&mut self, kind: GodotPieceKind) {
+        let commands = self.controller.choose_promotion(kind);
+        self.render(commands);
+    }
+
+    /// Applies the controller's view commands to the scene
+    fn render(&mut self, commands: Vec<ViewCommand>) {
+        for command in commands {
+            match command {
+                ViewCommand::Select { square, destinations } => {
+                    self.clear_helpers();
+                    self.move_select_square(&square);
+                    self.draw_helpers(&destinations);
+                    if let Some((_, kind)) = self.get_piece_on_square(&square) {
+                        self.announce_selection(kind, &square, destinations.len());
+                    }
+                }
+                ViewCommand::ClearSelection => {
+                    self.clear_helpers();
+                    self.hide_select_square();
+                }
+                ViewCommand::OpenPromotion(square) => {
+                    let turn = self.controller.get_turn();
+                    self.promotion_rect
+                        .bind_mut()
+                        .show(&square, turn, self.square_size, &self.player_color);
+                    self.base_mut()
+                        .emit_signal("promotion_requested", &[square.to_string().to_variant()]);
+                }
+                ViewCommand::ClosePromotion => self.promotion_rect.bind_mut().close(),
+                ViewCommand::PlayMove(legal_move) => self.play_engine_move(&legal_move),
+                ViewCommand::IllegalMove { from, to } => self.report_illegal_move(&from, &to),
+            }
+        }
     }
 
     fn init_board(&mut self) {
-        let mut board = GodotBoard::new_alloc();
+        let mut board = self.board_background.clone();
         board.bind_mut().set_square_size(self.square_size);
         board.bind_mut().ready();
         self.base_mut().add_child(&board);
-        self.board_background = board;
     }
 
     fn init_select_square(&mut self) {
-        let mut select_square = GodotSelectSquare::new_alloc();
+        let mut select_square = self.select_square.clone();
         select_square.set_size(Vector2::new(self.square_size, self.square_size));
         self.base_mut().add_child(&select_square);
-        self.select_square = select_square;
+        self.apply_marker_colors();
+    }
+
+    fn init_focus_cursor(&mut self) {
+        let mut focus_cursor = self.focus_cursor.clone();
+        focus_cursor.set_size(Vector2::new(self.square_size, self.square_size));
+        self.base_mut().add_child(&focus_cursor);
+        self.apply_marker_colors();
+    }
+
+    fn apply_marker_colors(&mut self) {
+        let (select_color, focus_color) = if self.high_contrast {
+            (HIGH_CONTRAST_SELECT_BORDER_COLOR, HIGH_CONTRAST_FOCUS_BORDER_COLOR)
+        } else {
+            (SELECT_BORDER_COLOR, FOCUS_BORDER_COLOR)
+        };
+        self.select_square.bind_mut().set_border_color_html(select_color);
+        self.focus_cursor.bind_mut().set_border_color_html(focus_color);
+        if let Some(hint) = self.hint {
+            let text = self.hint_label.get_text().to_string();
+            self.show_hint(&hint, &text);
+        }
+    }
+
+    fn get_legal_move_helper_color(&self) -> &'static str {
+        if self.high_contrast {
+            HIGH_CONTRAST_LEGAL_MOVE_HELPER_COLOR
+        } else {
+            LEGAL_MOVE_HELPER_COLOR
+        }
     }
 
     fn init_promotion_rect(&mut self) {
-        let mut promotion_rect = PromotionRect::new_alloc();
-        promotion_rect.bind_mut().set(self.player_color, self.square_size);
+        let mut promotion_rect = self.promotion_rect.clone();
+        promotion_rect.bind_mut().set(self.square_size);
         self.base_mut().add_child(&promotion_rect);
-        self.promotion_rect = promotion_rect;
+    }
+
+    fn init_hint(&mut self) {
+        let mut hint_arrow = self.hint_arrow.clone();
+        hint_arrow.set_visible(false);
+        self.base_mut().add_child(&hint_arrow);
+
+        let mut hint_label = self.hint_label.clone();
+        hint_label.set_position(Vector2::new(self.square_size * 8. + 20., 330.));
+        hint_label.set_visible(false);
+        self.base_mut().add_child(&hint_label);
+    }
+
+    fn show_hint(&mut self, hint: &GodotMove, text: &str) {
+        let color = if self.high_contrast {
+            HIGH_CONTRAST_HINT_ARROW_COLOR
+        } else {
+            HINT_ARROW_COLOR
+        };
+        let from = hint.get_origin().get_ui_vector2(self.square_size, &self.player_color);
+        let to = hint.get_destination().get_ui_vector2(self.square_size, &self.player_color);
+        self.hint_arrow.bind_mut().point(from, to, self.square_size, color);
+        self.hint_label.set_text(text);
+        self.hint_label.set_visible(true);
+        self.hint = Some(*hint);
+    }
+
+    /// Shows the hint once its search is done
+    fn poll_hint(&mut self) {
+        let Some(result) = self.hint_search.as_ref().and_then(|search| search.try_take()) else {
+            return;
+        };
+        self.hint_search = None;
+        let (Some(result), Some(engine)) = (result, self.engine.as_mut()) else {
+            return;
+        };
+        let hint = result.best_move;
+        let san = engine.get_san(&hint);
+        let score = result.format_score();
+        self.hints_used += 1;
+        self.record.add_comment(&format!("Hint: {} {}", san, score));
+        self.show_hint(&hint, &format!("Hint: {} ({})", san, score));
+        self.announcer
+            .speak(&format!("Hint: {} to {}", hint.get_origin(), hint.get_destination()));
+        self.base_mut().emit_signal(
+            "hint_shown",
+            &[san.to_variant(), hint.to_uci().to_variant(), score.to_variant()],
+        );
+    }
+
+    fn clear_hint(&mut self) {
+        self.hint = None;
+        self.hint_search = None;
+        self.hint_arrow.set_visible(false);
+        self.hint_label.set_visible(false);
+    }
+
+    fn init_eval_bar(&mut self) {
+        let board_size = self.square_size * 8.;
+        let mut eval_bar = self.eval_bar.clone();
+        eval_bar.bind_mut().set(
+            Vector2::new(board_size + 2., 0.),
+            Vector2::new(EVAL_BAR_WIDTH, board_size),
+            Vector2::new(EVAL_BAR_WIDTH + 4., 290.),
+        );
+        self.base_mut().add_child(&eval_bar);
+    }
+
+    fn is_eval_bar_shown(&self) -> bool {
+        self.show_eval_bar && !self.rated
+    }
+
+    /// Shows or hides the eval bar and evaluates the current position if it's showing
+    fn update_eval_bar(&mut self) {
+        let shown = self.is_eval_bar_shown();
+        self.eval_bar.set_visible(shown);
+        if shown && self.eval_search.is_none() && !self.controller.is_game_over() {
+            self.start_evaluation();
+        } else if !shown {
+            self.eval_search = None;
+        }
+    }
+
+    /// Evaluates the current position in the background, abandoning the search of the previous one. Positions in the
+    /// tablebases are looked up instead.
+    fn start_evaluation(&mut self) {
+        self.eval_search = None;
+        let Some(engine) = self.engine.as_ref().filter(|_| self.is_eval_bar_shown()) else {
+            return;
+        };
+        match self.probe_tablebases() {
+            Some(probe) => {
+                let white_evaluation = match engine.get_turn() {
+                    GodotPieceColor::White => probe.evaluation,
+                    GodotPieceColor::Black => probe.evaluation.flipped(),
+                };
+                self.show_evaluation(white_evaluation);
+            }
+            None => self.eval_search = Some(engine.search_in_background(EVAL_SEARCH_DEPTH)),
+        }
+    }
+
+    /// What the tablebases know about the current position, from the side to move's point of view
+    fn probe_tablebases(&self) -> Option<TablebaseProbe> {
+        let tablebases = self.tablebases.as_ref()?;
+        tablebases.probe(&fen::get_fen(self.record.get_start_fen(), self.engine.as_ref()?)?)
+    }
+
+    fn show_evaluation(&mut self, white_evaluation: Evaluation) {
+        self.eval_bar
+            .bind_mut()
+            .show_evaluation(white_evaluation.get_bar_fraction(), &white_evaluation.to_string());
+    }
+
+    /// Searches the current position for the multi-PV panel if it's on, abandoning the previous search
+    fn restart_multi_pv(&mut self) {
+        self.clear_line_preview();
+        self.multi_pv_lines.clear();
+        let searching = self.analysis_mode && self.multi_pv_line_count > 0 && !self.controller.is_game_over();
+        match self.engine.as_ref().filter(|_| searching) {
+            Some(engine) => self.multi_pv.get_or_insert_with(MultiPvSearch::new).start(
+                engine.copy_position(),
+                self.multi_pv_line_count,
+                MULTI_PV_MAX_DEPTH,
+            ),
+            None => {
+                if let Some(search) = self.multi_pv.as_mut() {
+                    search.stop();
+                }
+            }
+        }
+    }
+
+    fn show_multi_pv(&mut self, update: MultiPvUpdate) {
+        let lines: Array<Dictionary> = update
+            .lines
+            .iter()
+            .map(|line| {
+                let mut output = Dictionary::new();
+                output.set("eval", line.evaluation.to_string());
+                output.set("depth", line.depth as i32);
+                output.set(
+                    "san",
+                    line.sans
+                        .iter()
+                        .map(|san| GString::from(san.as_str()))
+                        .collect::<PackedStringArray>(),
+                );
+                output.set(
+                    "uci",
+                    line.moves
+                        .iter()
+                        .map(|line_move| GString::from(line_move.to_uci()))
+                        .collect::<PackedStringArray>(),
+                );
+                output
+            })
+            .collect();
+        let nodes_per_second = update.get_nodes_per_second() as i64;
+        self.multi_pv_lines = update.lines;
+        self.base_mut().emit_signal(
+            "multi_pv_updated",
+            &[
+                lines.to_variant(),
+                (update.depth as i32).to_variant(),
+                (update.nodes as i64).to_variant(),
+                nodes_per_second.to_variant(),
+                (update.elapsed.as_millis() as i64).to_variant(),
+            ],
+        );
+    }
+
+    fn show_ghost_piece(&mut self, kind: GodotPieceKind, color: GodotPieceColor, square: &GodotSquare) {
+        let mut ghost_piece = match self.ghost_pieces.get(self.ghost_piece_count) {
+            Some(ghost_piece) => ghost_piece.clone(),
+            None => {
+                let mut ghost_piece = GodotPiece::new_alloc();
+                ghost_piece.set_modulate(Color::from_rgba(1., 1., 1., GHOST_PIECE_ALPHA));
+                self.base_mut().add_child(&ghost_piece);
+                self.ghost_pieces.push(ghost_piece.clone());
+                ghost_piece
+            }
+        };
+        ghost_piece.bind_mut().set_piece(kind, color, self.square_size);
+        ghost_piece.set_position(square.get_ui_vector2(self.square_size, &self.player_color));
+        ghost_piece.bind_mut().set_image();
+        ghost_piece.set_visible(true);
+        self.ghost_piece_count += 1;
+    }
+
+    fn poll_analysis(&mut self) {
+        let Some((key, job)) = self.analysis_job.as_ref() else {
+            return;
+        };
+        let (done, total) = job.get_progress();
+        let finished = job.try_take().map(|analysis| (key.clone(), analysis));
+
+        if done != self.analysis_progress {
+            self.analysis_progress = done;
+            self.base_mut().emit_signal(
+                "analysis_progress",
+                &[(done as i32).to_variant(), (total as i32).to_variant()],
+            );
+        }
+        if let Some((key, analysis)) = finished {
+            analysis.save(&key);
+            self.analysis = Some((key, analysis));
+            self.analysis_job = None;
+            self.base_mut().emit_signal("analysis_finished", &[]);
+        }
+    }
+
+    fn get_player_name(&self, color: GodotPieceColor) -> String {
+        match self.opponent.as_ref() {
+            Some(opponent) if self.bot_color == Some(color) => opponent.get_name(),
+            _ => "Player".to_string(),
+        }
     }
 
     fn hide_select_square(&mut self) {
@@ -305,113 +1214,417 @@ impl GodotGame {
         self.select_square.set_visible(true);
     }
 
-    fn init_pieces(&mut self) {
-        let pieces = self.engine.get_pieces_per_square(&self.player_color);
-        for (square, color, kind) in pieces {
-            self.init_piece(kind, color, &square);
-        }
+    /// Drops the selection and brings the piece nodes in line with the engine board
+    fn reset_board_state(&mut self) {
+        self.clear_hint();
+        self.promotion_rect.bind_mut().close();
+        self.clear_helpers();
+        self.hide_select_square();
+
+        self.sync_pieces();
+        self.update_controller_position();
+        self.start_evaluation();
+        self.restart_multi_pv();
     }
 
     fn init_piece(&mut self, kind: GodotPieceKind, color: GodotPieceColor, square: &GodotSquare) {
-        let mut piece = GodotPiece::new_alloc();
+        let mut piece = match self.piece_pool.pop() {
+            Some(piece) => piece,
+            None => {
+                let piece = GodotPiece::new_alloc();
+                self.base_mut().add_child(&piece);
+                // Keep pieces just above the board and below the selection markers, also when created mid-game
+                self.base_mut().move_child(&piece, 1);
+                piece
+            }
+        };
         piece.bind_mut().set_piece(kind, color, self.square_size);
         piece.set_position(square.get_ui_vector2(self.square_size, &self.player_color));
         piece.bind_mut().set_image();
-
-        self.base_mut().add_child(&piece);
+        piece.set_visible(true);
         self.pieces[square.get_field_index(&self.player_color)] = Some(piece);
     }
 
-    fn init_sounds(&mut self) {
-        let sound_move = GodotSounds::from_path(&format!(
-            "{}{}{}",
-            RESOURCES_FOLDER_PATH, SOUNDS_SUBFOLDER_PATH, MOVE_SOUND_FILE_NAME
-        ));
-        self.base_mut().add_child(&sound_move.bind().player);
-        self.sound_move = sound_move;
-        let sound_capture = GodotSounds::from_path(&format!(
-            "{}{}{}",
-            RESOURCES_FOLDER_PATH, SOUNDS_SUBFOLDER_PATH, CAPTURE_SOUND_FILE_NAME
-        ));
-        self.base_mut().add_child(&sound_capture.bind().player);
-        self.sound_capture = sound_capture;
+    fn release_piece(&mut self, mut piece: Gd<GodotPiece>) {
+        piece.set_visible(false);
+        self.piece_pool.push(piece);
     }
 
-    fn draw_helpers(&mut self, helpers: HashSet<GodotMove>) {
-        for helper_move in helpers {
-            // only draw one helper for promotion, even though those are 4 separate moves
-            if helper_move.is_promotion() && helper_move.get_promotion_piece() != 0 {
-                continue;
-            }
+    /// Recovers from the pieces on screen disagreeing with the engine by rebuilding them from the engine board
+    fn resync_board(&mut self, reason: &str) {
+        godot_error!("Board out of sync, rebuilding it: {}", reason);
+        self.reset_board_state();
+        self.base_mut().emit_signal("board_resynced", &[reason.to_variant()]);
+    }
 
-            let helper_position = helper_move.get_destination();
-            let mut helper = LegalMoveHelper::new_alloc();
+    fn init_sounds(&mut self) {
+        self.sounds = SoundBank::load();
+        for player in self.sounds.get_players() {
+            self.base_mut().add_child(&player);
+        }
+    }
+
+    fn draw_helpers(&mut self, destinations: &[GodotSquare]) {
+        if !self.show_legal_moves {
+            return;
+        }
+        for helper_position in destinations {
+            let mut helper = match self.legal_move_helpers.get(self.legal_move_helper_count) {
+                Some(helper) => helper.clone(),
+                None => {
+                    let helper = LegalMoveHelper::new_alloc();
+                    self.base_mut().add_child(&helper);
+                    self.legal_move_helpers.push(helper.clone());
+                    helper
+                }
+            };
             helper.bind_mut().create(
                 helper_position.get_ui_vector2(self.square_size, &self.player_color),
                 self.square_size,
+                self.get_legal_move_helper_color(),
             );
-            self.base_mut().add_child(&helper);
-            self.legal_move_helpers.push(helper);
+            self.legal_move_helper_count += 1;
         }
     }
 
     fn clear_helpers(&mut self) {
-        while let Some(helper) = self.legal_move_helpers.pop() {
-            self.base_mut().remove_child(&helper);
+        for helper in self.legal_move_helpers[..self.legal_move_helper_count].iter_mut() {
+            helper.set_visible(false);
         }
+        self.legal_move_helper_count = 0;
     }
 
-    fn move_piece(&mut self, from: &GodotSquare, to: &GodotSquare) {
-        let from_index = from.get_field_index(&self.player_color);
-        let selected_piece = self.pieces[from_index].as_mut().unwrap();
-        selected_piece.set_position(to.get_ui_vector2(self.square_size, &self.player_color));
-        self.pieces[to.get_field_index(&self.player_color)] = self.pieces[from_index].clone();
-        self.pieces[from_index] = None;
-    }
+    /// Moves, adds and removes piece nodes until they match the engine board. Any kind of move, undo or newly
+    /// loaded position is handled the same way, with pieces sliding to their new squares where possible.
+    fn sync_pieces(&mut self) {
+        let mut target: [Option<(GodotPieceColor, GodotPieceKind)>; 64] = [None; 64];
+        let pieces = self
+            .engine
+            .as_ref()
+            .map_or(Vec::new(), |engine| engine.get_pieces_per_square(&self.player_color));
+        for (square, color, kind) in pieces {
+            target[square.get_field_index(&self.player_color)] = Some((color, kind));
+        }
 
-    fn play_move_sound(&mut self) {
-        self.sound_move.bind_mut().player.play();
-    }
+        // Nodes not already showing the right piece are free to move elsewhere
+        let mut spare_pieces: Vec<Gd<GodotPiece>> = Vec::new();
+        for (index, target_piece) in target.iter().enumerate() {
+            let is_correct = self.pieces[index].as_ref().is_some_and(|piece| {
+                let piece = piece.bind();
+                *target_piece == Some((piece.color, piece.kind))
+            });
+            if !is_correct {
+                spare_pieces.extend(self.pieces[index].take());
+            }
+        }
 
-    fn play_capture_sound(&mut self) {
-        self.sound_capture.bind_mut().player.play();
+        let mut missing_pieces = Vec::new();
+        for (index, target_piece) in target.iter().enumerate() {
+            let Some((color, kind)) = *target_piece else {
+                continue;
+            };
+            if self.pieces[index].is_some() {
+                continue;
+            }
+            let square = GodotSquare::from_field_index(index, &self.player_color);
+            let target_position = square.get_ui_vector2(self.square_size, &self.player_color);
+            let closest_spare = spare_pieces
+                .iter()
+                .enumerate()
+                .filter(|(_, piece)| {
+                    let piece = piece.bind();
+                    piece.color == color && piece.kind == kind
+                })
+                .min_by(|(_, a), (_, b)| {
+                    let distance_a = a.get_position().distance_squared_to(target_position);
+                    let distance_b = b.get_position().distance_squared_to(target_position);
+                    distance_a.total_cmp(&distance_b)
+                })
+                .map(|(spare_index, _)| spare_index);
+            match closest_spare {
+                Some(spare_index) => {
+                    let piece = spare_pieces.swap_remove(spare_index);
+                    self.slide_piece(piece.clone(), target_position);
+                    self.pieces[index] = Some(piece);
+                }
+                None => missing_pieces.push((kind, color, square)),
+            }
+        }
+
+        // Captured pieces and promoted pawns go back to the pool before promoted pieces are taken from it
+        for piece in spare_pieces {
+            self.release_piece(piece);
+        }
+        for (kind, color, square) in missing_pieces {
+            self.init_piece(kind, color, &square);
+        }
     }
 
-    fn get_legal_move_from_origin_and_destination(&self, from: &GodotSquare, to: &GodotSquare) -> Option<GodotMove> {
-        match self.legal_moves.get(from) {
-            Some(legal_moves) => legal_moves
-                .iter()
-                .filter(|legal_move| legal_move.get_destination() == *to)
-                .nth(0)
-                .copied(),
-            None => None,
+    fn slide_piece(&mut self, mut piece: Gd<GodotPiece>, target_position: Vector2) {
+        match piece.create_tween() {
+            Some(mut tween) if self.animation_duration > 0. => {
+                tween.tween_property(
+                    &piece,
+                    &NodePath::from("position"),
+                    &target_position.to_variant(),
+                    self.animation_duration,
+                );
+            }
+            _ => piece.set_position(target_position),
         }
     }
 
-    fn move_rook_for_castling(&mut self, castling_move: &GodotMove) {
-        let king_destination = castling_move.get_destination();
-        let king_destination_square = king_destination.get_square();
-        let (rook_origin_file, rook_destination_file) = if king_destination_square.get_file() == 2 {
-            // long
-            (0, 3)
+    /// Plays the most specific sound for a move that was just made on the engine board
+    fn play_move_sounds(&mut self, legal_move: &GodotMove, is_capture: bool) {
+        let event = if self.is_in_check() {
+            SoundEvent::Check
+        } else if legal_move.is_promotion() {
+            SoundEvent::Promote
+        } else if legal_move.is_castling() {
+            SoundEvent::Castle
+        } else if is_capture {
+            SoundEvent::Capture
         } else {
-            // Short
-            (7, 5)
+            SoundEvent::Move
+        };
+        self.sounds.play(event);
+    }
+
+    fn init_clock(&mut self, time_control: Option<TimeControl>) {
+        let Some(time_control) = time_control else {
+            return;
         };
-        let rook_rank = king_destination_square.get_rank();
+        self.clock = Some(GameClock::new(time_control));
 
-        let rook_origin = GodotSquare::from_engine_square(Square::new(rook_rank * 8 + rook_origin_file));
-        let rook_destination = GodotSquare::from_engine_square(Square::new(rook_rank * 8 + rook_destination_file));
+        let x = self.square_size * 8. + 20.;
+        let top_color = self.player_color.opponent_turn();
+        for (color, y) in [(top_color, 20.), (self.player_color, self.square_size * 8. - 60.)] {
+            let mut label = Label::new_alloc();
+            label.set_position(Vector2::new(x, y));
+            label.add_theme_font_size_override("font_size", 32);
+            self.base_mut().add_child(&label);
+            self.clock_labels.push((color, label));
+        }
+        self.update_clock_labels();
+    }
 
-        self.move_piece(&rook_origin, &rook_destination);
+    fn update_clock_labels(&mut self) {
+        let Some(clock) = self.clock.as_ref() else {
+            return;
+        };
+        for (color, label) in self.clock_labels.iter_mut() {
+            label.set_text(&GameClock::format(clock.get_remaining_seconds(color)));
+        }
     }
 
     fn end_turn(&mut self) {
+        let mover = self.controller.get_turn();
+        if let Some(clock) = self.clock.as_mut() {
+            clock.press(&mover);
+        }
+        self.update_clock_labels();
+        self.hide_select_square();
+        self.clear_helpers();
+        self.promotion_rect.bind_mut().close();
+        self.update_controller_position();
+        self.start_evaluation();
+        self.restart_multi_pv();
+
+        let turn = self.controller.get_turn();
+        self.base_mut().emit_signal("turn_changed", &[turn.name().to_variant()]);
+
+        let is_check = self.is_in_check();
+        if is_check {
+            self.base_mut().emit_signal("check", &[turn.name().to_variant()]);
+        }
+        if self.controller.get_legal_moves().is_empty() {
+            if is_check {
+                self.finish_game(turn.opponent_turn().get_win_result(), "checkmate");
+            } else {
+                self.finish_game("1/2-1/2", "stalemate");
+            }
+            return;
+        }
+        if self.tablebase_adjudication
+            && let Some(probe) = self.probe_tablebases()
+        {
+            let result = match probe.evaluation {
+                Evaluation::TablebaseWin(moves) if moves > 0 => turn.get_win_result(),
+                Evaluation::TablebaseWin(_) => turn.opponent_turn().get_win_result(),
+                _ => "1/2-1/2",
+            };
+            self.finish_game(result, "tablebase adjudication");
+            return;
+        }
+        self.schedule_bot_move();
+    }
+
+    /// Hands the controller the side to move and its legal moves on the engine board
+    fn update_controller_position(&mut self) {
+        if let Some(engine) = self.engine.as_mut() {
+            self.controller.set_position(engine.get_turn(), engine.get_legal_moves());
+        }
+    }
+
+    fn get_piece_on_square(&self, square: &GodotSquare) -> Option<(GodotPieceColor, GodotPieceKind)> {
+        self.engine.as_ref()?.get_piece_on_square(square)
+    }
+
+    fn is_in_check(&self) -> bool {
+        self.engine.as_ref().is_some_and(ChessEngine::is_in_check)
+    }
+
+    fn is_human_turn(&self) -> bool {
+        self.bot_color != Some(self.controller.get_turn())
+    }
+
+    fn schedule_bot_move(&mut self) {
+        if !self.controller.is_game_over() && !self.is_human_turn() {
+            self.bot_move_countdown = Some(BOT_MOVE_DELAY_SECONDS);
+        }
+    }
+
+    fn play_bot_move(&mut self) {
+        if self.controller.is_game_over() || self.is_human_turn() {
+            return;
+        }
+        let (Some(opponent), Some(engine)) = (self.opponent.as_mut(), self.engine.as_ref()) else {
+            return;
+        };
+        let clock = self.clock.as_ref().map(|clock| ClockState {
+            white_seconds: clock.get_remaining_seconds(&GodotPieceColor::White),
+            black_seconds: clock.get_remaining_seconds(&GodotPieceColor::Black),
+            increment_seconds: clock.get_increment_seconds(),
+        });
+        let moves = engine.get_played_moves();
+        let request = MoveRequest {
+            engine,
+            start_fen: self.record.get_start_fen(),
+            moves: &moves,
+            clock,
+        };
+        if let Err(reason) = opponent.request_move(&request) {
+            self.forfeit_opponent(&reason);
+        }
+    }
+
+    /// Hands on what the opponent has to say, and plays its move once it's chosen
+    fn poll_opponent(&mut self) {
+        let Some(opponent) = self.opponent.as_mut() else {
+            return;
+        };
+        let events = match opponent.poll() {
+            Ok(events) => events,
+            Err(reason) => {
+                self.forfeit_opponent(&reason);
+                return;
+            }
+        };
+        for event in events {
+            match event {
+                OpponentEvent::Info(text) => {
+                    self.base_mut().emit_signal("opponent_info", &[text.to_variant()]);
+                }
+                // A move for a position that was taken back
+                OpponentEvent::Move(_) | OpponentEvent::BookMove(_) if self.controller.is_game_over() || self.is_human_turn() => {
+                }
+                OpponentEvent::Move(uci) => {
+                    if !self.make_move(uci.clone()) {
+                        self.forfeit_opponent(&format!("illegal move {}", uci));
+                        return;
+                    }
+                }
+                OpponentEvent::BookMove(uci) => {
+                    self.record.add_comment("Book");
+                    if !self.make_move(uci.clone()) {
+                        self.forfeit_opponent(&format!("illegal move {}", uci));
+                        return;
+                    }
+                    self.base_mut().emit_signal("book_move_played", &[uci.to_variant()]);
+                }
+            }
+        }
+    }
+
+    /// The bot's opening book from the settings. `None` when it's turned off or missing, which only gets reported if
+    /// the book is there but can't be used.
+    fn load_opening_book(settings: &GodotSettings) -> Option<(OpeningBook, BookOptions)> {
+        let path = settings.book_path.to_string();
+        if settings.book_max_plies <= 0 || path.is_empty() || !FileAccess::file_exists(&path) {
+            return None;
+        }
+        match OpeningBook::from_bytes(&FileAccess::get_file_as_bytes(&path).to_vec()) {
+            Ok(book) => {
+                let options = BookOptions {
+                    variety: settings.book_variety as f64,
+                    max_plies: settings.book_max_plies as usize,
+                };
+                Some((book, options))
+            }
+            Err(error) => {
+                godot_error!("Opening book disabled, {} is invalid: {}", path, error);
+                None
+            }
+        }
+    }
+
+    /// The Syzygy tables from the settings, `None` when no folder is set or it can't be used
+    fn load_tablebases(settings: &GodotSettings) -> Option<Arc<Tablebases>> {
+        let path = settings.syzygy_path.to_string();
+        if path.is_empty() {
+            return None;
+        }
+        // Folders in `user://` or `res://` are read straight from the file system
+        let folder = ProjectSettings::singleton().globalize_path(&path).to_string();
+        match Tablebases::open(&folder) {
+            Ok(tablebases) => Some(Arc::new(tablebases)),
+            Err(error) => {
+                godot_warn!("Tablebases disabled: {}", error);
+                None
+            }
+        }
+    }
+
+    /// Ends the game in the player's favour when the computer opponent can't go on
+    fn forfeit_opponent(&mut self, reason: &str) {
+        godot_error!("Opponent failed: {}", reason);
+        let Some(bot_color) = self.bot_color else {
+            return;
+        };
+        self.base_mut().emit_signal("opponent_failed", &[reason.to_variant()]);
+        if !self.controller.is_game_over() {
+            self.finish_game(bot_color.opponent_turn().get_win_result(), "opponent failure");
+        }
+    }
+
+    fn finish_game(&mut self, result: &str, reason: &str) {
+        self.controller.set_game_over(true);
+        if let Some(opponent) = self.opponent.as_mut() {
+            opponent.cancel();
+        }
+        self.record.set_result(result);
+        self.clear_hint();
+        self.eval_search = None;
+        self.restart_multi_pv();
+        let white_fraction = match result {
+            "1-0" => 1.,
+            "0-1" => 0.,
+            _ => 0.5,
+        };
+        self.eval_bar.bind_mut().show_evaluation(white_fraction, result);
+        self.clear_helpers();
         self.hide_select_square();
-        self.legal_moves.clear();
-        self.turn = self.turn.opponent_turn();
-        self.selected_piece_square = None;
-        self.selected_piece_kind = None;
-        self.promotion_rect.hide();
+        self.sounds.play(SoundEvent::GameEnd);
+        self.announcer.speak(&format!("Game over by {}, {}", reason, result));
+        self.base_mut()
+            .emit_signal("game_over", &[result.to_variant(), reason.to_variant()]);
+    }
+
+    fn report_illegal_move(&mut self, from: &GodotSquare, to: &GodotSquare) {
+        self.sounds.play(SoundEvent::IllegalMove);
+        self.base_mut().emit_signal(
+            "illegal_move_attempted",
+            &[from.to_string().to_variant(), to.to_string().to_variant()],
+        );
     }
 }
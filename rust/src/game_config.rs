@@ -0,0 +1,197 @@
+use std::path::Path;
+
+use godot::global::randi;
+use godot::prelude::*;
+
+use crate::beginner::Personality;
+use crate::chess_pieces::GodotPieceColor;
+use crate::clock::TimeControl;
+use crate::consts::{DEFAULT_BOT_LEVEL, MAX_BOT_LEVEL, MIN_BOT_LEVEL};
+use crate::engine::ChessEngine;
+
+/// Everything needed to start a game. All fields are strings so the resource can be filled in from menus
+/// and saved with `ResourceSaver`; `validate` reports what's wrong with it.
+#[derive(GodotClass)]
+#[class(init, base=Resource)]
+pub struct GameConfig {
+    /// `white`, `black` or `random`
+    #[export]
+    #[init(val = GString::from("white"))]
+    pub side: GString,
+    /// `human` for hot-seat play, `bot`, `uci`, or one of the beginner bots `random`, `greedy`, `cautious` and
+    /// `blunderer`
+    #[export]
+    #[init(val = GString::from("human"))]
+    pub opponent: GString,
+    #[export]
+    #[init(val = DEFAULT_BOT_LEVEL)]
+    pub bot_level: i32,
+    /// Chance between 0 and 1 that the `blunderer` throws a move away
+    #[export]
+    #[init(val = 0.25)]
+    pub blunder_chance: f32,
+    /// Executable of the engine when the opponent is `uci`
+    #[export]
+    pub uci_engine_path: GString,
+    /// Empty for the initial position
+    #[export]
+    pub fen: GString,
+    /// Only `standard` is supported
+    #[export]
+    #[init(val = GString::from("standard"))]
+    pub variant: GString,
+    /// `minutes+increment`, `none`, or empty to use the time control from the settings
+    #[export]
+    pub time_control: GString,
+    /// `white`, `black`, or `side` to put the player's pieces at the bottom
+    #[export]
+    #[init(val = GString::from("side"))]
+    pub orientation: GString,
+    /// Untimed free play for both sides, for studying rather than competing
+    #[export]
+    pub analysis: bool,
+    /// A game against the computer played for real, without the eval bar or hints
+    #[export]
+    pub rated: bool,
+    base: Base<Resource>,
+}
+
+pub enum OpponentKind {
+    Human,
+    /// The built-in bot at a level
+    Bot(u8),
+    /// Path of a UCI engine executable
+    Uci(String),
+    Beginner(Personality),
+}
+
+pub struct ResolvedGameConfig {
+    pub side: GodotPieceColor,
+    pub opponent: OpponentKind,
+    pub engine: ChessEngine,
+    /// `None` for the initial position
+    pub fen: Option<String>,
+    pub time_control: Option<TimeControl>,
+    pub orientation: GodotPieceColor,
+    pub analysis: bool,
+    pub rated: bool,
+}
+
+#[godot_api]
+impl GameConfig {
+    /// Returns an empty string for a valid config, otherwise a description of the first problem found
+    #[func]
+    pub fn validate(&self) -> GString {
+        match self.resolve("none") {
+            Ok(_) => GString::new(),
+            Err(error) => error.into(),
+        }
+    }
+
+    pub fn resolve(&self, default_time_control: &str) -> Result<ResolvedGameConfig, String> {
+        let side = match self.side.to_string().as_str() {
+            "white" => GodotPieceColor::White,
+            "black" => GodotPieceColor::Black,
+            "random" if randi() % 2 == 0 => GodotPieceColor::White,
+            "random" => GodotPieceColor::Black,
+            other => return Err(format!("Unknown side '{}', expected white, black or random", other)),
+        };
+
+        let opponent = match self.opponent.to_string().as_str() {
+            "human" => OpponentKind::Human,
+            "bot" if (MIN_BOT_LEVEL..=MAX_BOT_LEVEL).contains(&self.bot_level) => OpponentKind::Bot(self.bot_level as u8),
+            "bot" => {
+                return Err(format!(
+                    "Bot level {} is out of range, expected {} to {}",
+                    self.bot_level, MIN_BOT_LEVEL, MAX_BOT_LEVEL
+                ));
+            }
+            "uci" if self.uci_engine_path.is_empty() => return Err("No UCI engine is set up".to_string()),
+            "uci" if !Path::new(&self.uci_engine_path.to_string()).is_file() => {
+                return Err(format!("UCI engine '{}' not found", self.uci_engine_path));
+            }
+            "uci" => OpponentKind::Uci(self.uci_engine_path.to_string()),
+            "random" => OpponentKind::Beginner(Personality::Random),
+            "greedy" => OpponentKind::Beginner(Personality::Greedy),
+            "cautious" => OpponentKind::Beginner(Personality::Cautious),
+            "blunderer" if (0. ..=1.).contains(&self.blunder_chance) => {
+                OpponentKind::Beginner(Personality::Blunderer(self.blunder_chance as f64))
+            }
+            "blunderer" => {
+                return Err(format!(
+                    "Blunder chance {} is out of range, expected 0 to 1",
+                    self.blunder_chance
+                ));
+            }
+            other => {
+                return Err(format!(
+                    "Unknown opponent '{}', expected human, bot, uci, random, greedy, cautious or blunderer",
+                    other
+                ));
+            }
+        };
+
+        if self.variant.to_string() != "standard" {
+            return Err(format!("Unsupported variant '{}'", self.variant));
+        }
+
+        // Checked first so a missing table isn't reported as an invalid FEN
+        ChessEngine::check_tables()?;
+        let engine = if self.fen.is_empty() {
+            ChessEngine::new()?
+        } else {
+            ChessEngine::from_fen(&self.fen.to_string()).map_err(|error| format!("Invalid FEN: {}", error))?
+        };
+
+        let fen = (!self.fen.is_empty()).then(|| self.fen.to_string());
+
+        let time_control_string = if self.time_control.is_empty() {
+            default_time_control.to_string()
+        } else {
+            self.time_control.to_string()
+        };
+        let time_control = match time_control_string.as_str() {
+            "" | "none" => None,
+            other => Some(TimeControl::parse(other).ok_or(format!("Invalid time control '{}', expected e.g. 5+3", other))?),
+        };
+
+        let orientation = match self.orientation.to_string().as_str() {
+            "side" => side,
+            "white" => GodotPieceColor::White,
+            "black" => GodotPieceColor::Black,
+            other => return Err(format!("Unknown orientation '{}', expected side, white or black", other)),
+        };
+
+        if self.rated && matches!(opponent, OpponentKind::Human) {
+            return Err("Rated games need a computer opponent".to_string());
+        }
+        if self.rated && self.analysis {
+            return Err("Analysis games can't be rated".to_string());
+        }
+
+        // Analysis is untimed free play for both sides
+        if self.analysis {
+            return Ok(ResolvedGameConfig {
+                side,
+                opponent: OpponentKind::Human,
+                engine,
+                fen,
+                time_control: None,
+                orientation,
+                analysis: true,
+                rated: false,
+            });
+        }
+
+        Ok(ResolvedGameConfig {
+            side,
+            opponent,
+            engine,
+            fen,
+            time_control,
+            orientation,
+            analysis: false,
+            rated: self.rated,
+        })
+    }
+}
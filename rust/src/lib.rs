@@ -1,15 +1,47 @@
+#[cfg(feature = "godot")]
+mod accessibility;
+#[cfg(feature = "godot")]
+mod analysis;
+pub mod beginner;
+pub mod book;
+#[cfg(feature = "godot")]
 mod chess_board;
-mod chess_pieces;
-mod consts;
-mod engine;
+pub mod chess_pieces;
+pub mod clock;
+pub mod consts;
+pub mod controller;
+#[cfg(feature = "godot")]
+mod editor;
+pub mod engine;
+#[cfg(feature = "godot")]
+mod eval_graph;
+pub mod fen;
+#[cfg(feature = "godot")]
 mod game;
-mod moves;
+#[cfg(feature = "godot")]
+mod game_config;
+pub mod moves;
+pub mod multi_pv;
+pub mod opponent;
+pub mod pgn;
+#[cfg(feature = "godot")]
+mod position;
+#[cfg(feature = "godot")]
+mod settings;
+pub mod setup;
+#[cfg(feature = "godot")]
 mod sounds;
-mod square;
+pub mod square;
+pub mod tablebase;
+pub mod tables;
+pub mod uci_engine;
 
+#[cfg(feature = "godot")]
 use godot::prelude::*;
 
+#[cfg(feature = "godot")]
 struct GodotChess;
 
+#[cfg(feature = "godot")]
 #[gdextension]
 unsafe impl ExtensionLibrary for GodotChess {}
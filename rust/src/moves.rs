@@ -6,10 +6,20 @@ use crate::square::GodotSquare;
 pub struct GodotMove(Move);
 
 impl GodotMove {
+    /// Indexed by the engine's promotion piece number
+    const PROMOTION_CHARS: [char; 4] = ['q', 'r', 'b', 'n'];
+
     pub fn from_engine_move(engine_move: Move) -> Self {
         Self(engine_move)
     }
 
+    pub fn from_origin_and_destination(origin: &GodotSquare, destination: &GodotSquare) -> Self {
+        Self(Move::from_origin_and_destination(
+            &destination.get_square(),
+            &origin.get_square(),
+        ))
+    }
+
     pub fn from_origin_destination_and_promotion(
         origin: &GodotSquare,
         destination: &GodotSquare,
@@ -20,6 +30,10 @@ impl GodotMove {
         Self::from_engine_move(engine_move)
     }
 
+    pub fn get_origin(&self) -> GodotSquare {
+        GodotSquare::from_engine_square(self.0.get_origin())
+    }
+
     pub fn get_destination(&self) -> GodotSquare {
         GodotSquare::from_engine_square(self.0.get_destination())
     }
@@ -39,4 +53,13 @@ impl GodotMove {
     pub fn is_castling(&self) -> bool {
         self.0.special_move() == 3
     }
+
+    /// Long algebraic notation as used by UCI, e.g. `e2e4` or `e7e8q`
+    pub fn to_uci(&self) -> String {
+        let mut output = format!("{}{}", self.get_origin(), self.get_destination());
+        if self.is_promotion() {
+            output.push(Self::PROMOTION_CHARS[self.get_promotion_piece()]);
+        }
+        output
+    }
 }
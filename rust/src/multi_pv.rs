@@ -0,0 +1,276 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::chess_pieces::GodotPieceColor;
+use crate::engine::{ChessEngine, Evaluation, MATE_SCORE};
+use crate::moves::GodotMove;
+
+/// Beyond any score, mates included
+const INFINITY: i32 = MATE_SCORE + 2;
+
+pub struct PvLine {
+    pub moves: Vec<GodotMove>,
+    pub sans: Vec<String>,
+    /// From White's point of view
+    pub evaluation: Evaluation,
+    /// Plies searched, the root move included
+    pub depth: u8,
+}
+
+/// The best lines found by one iteration of the search
+pub struct MultiPvUpdate {
+    pub lines: Vec<PvLine>,
+    /// Plies searched, the root move included
+    pub depth: u8,
+    /// Positions visited since the search started. Those on the last ply count once each, although the bot's one-ply
+    /// search scoring them also looks at their moves.
+    pub nodes: u64,
+    /// Since the search started
+    pub elapsed: Duration,
+}
+
+impl MultiPvUpdate {
+    pub fn get_nodes_per_second(&self) -> u64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds > 0. {
+            (self.nodes as f64 / seconds) as u64
+        } else {
+            0
+        }
+    }
+}
+
+/// Searches every legal move of a position one depth at a time, with alpha-beta down to the last ply and the bot's
+/// one-ply search there. A single thread searches every position handed to `start`, dropping the search of the
+/// previous one at the next position it visits, so browsing through a game doesn't pile up searches.
+pub struct MultiPvSearch {
+    /// Bumped by `start` and `stop`. The worker gives up on any search of an older position.
+    generation: Arc<AtomicU64>,
+    jobs: Sender<MultiPvJob>,
+    /// Updates with the generation of the position they belong to
+    receiver: Receiver<(u64, MultiPvUpdate)>,
+}
+
+/// A position for the worker thread to search
+struct MultiPvJob {
+    engine: ChessEngine,
+    generation: u64,
+    line_count: usize,
+    max_depth: u8,
+}
+
+impl Drop for MultiPvSearch {
+    /// The worker leaves its search and then its thread, as the job channel closes
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl Default for MultiPvSearch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MultiPvSearch {
+    pub fn new() -> Self {
+        let (jobs, job_receiver) = mpsc::channel::<MultiPvJob>();
+        let (sender, receiver) = mpsc::channel();
+        let generation = Arc::new(AtomicU64::new(0));
+        let latest_generation = generation.clone();
+        thread::spawn(move || {
+            while let Ok(mut job) = job_receiver.recv() {
+                // Positions passed over while searching an earlier one aren't worth starting on
+                while let Ok(newer_job) = job_receiver.try_recv() {
+                    job = newer_job;
+                }
+                MultiPvWorker {
+                    engine: job.engine,
+                    generation: job.generation,
+                    latest_generation: latest_generation.clone(),
+                    nodes: 0,
+                    start_time: Instant::now(),
+                }
+                .run(job.line_count, job.max_depth, &sender);
+            }
+        });
+        Self {
+            generation,
+            jobs,
+            receiver,
+        }
+    }
+
+    /// Searches `engine` from now on, instead of any position before
+    pub fn start(&mut self, engine: ChessEngine, line_count: usize, max_depth: u8) {
+        let generation = self.generation.fetch_add(1, Ordering::Relaxed) + 1;
+        // The worker only stops once this is dropped, so it's always there to receive
+        let _ = self.jobs.send(MultiPvJob {
+            engine,
+            generation,
+            line_count,
+            max_depth,
+        });
+    }
+
+    /// Gives up on the position being searched
+    pub fn stop(&mut self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The deepest update for the position of the last `start` that arrived since the last call
+    pub fn try_take_latest(&self) -> Option<MultiPvUpdate> {
+        let current = self.generation.load(Ordering::Relaxed);
+        self.receiver
+            .try_iter()
+            .filter(|(generation, _)| *generation == current)
+            .map(|(_, update)| update)
+            .last()
+    }
+}
+
+struct MultiPvWorker {
+    engine: ChessEngine,
+    /// Of the position being searched
+    generation: u64,
+    latest_generation: Arc<AtomicU64>,
+    nodes: u64,
+    start_time: Instant,
+}
+
+impl MultiPvWorker {
+    fn run(&mut self, line_count: usize, max_depth: u8, sender: &Sender<(u64, MultiPvUpdate)>) {
+        let mut root_moves: Vec<GodotMove> = self.engine.get_legal_moves().into_values().flatten().collect();
+        root_moves.sort_by_key(|root_move| root_move.to_uci());
+        let turn = self.engine.get_turn();
+
+        for depth in 1..=max_depth {
+            // `depth` plies are searched after each root move
+            let line_depth = depth + 1;
+            // Scores from the point of view of the side to move at the root, best first
+            let mut scored_moves: Vec<(GodotMove, i32)> = Vec::with_capacity(root_moves.len());
+            for root_move in root_moves.iter() {
+                // A move only has to be shown worse than the last line once there are enough lines
+                let floor = line_count
+                    .checked_sub(1)
+                    .and_then(|index| scored_moves.get(index))
+                    .map_or(-INFINITY, |(_, score)| *score);
+                self.engine.play_move(root_move);
+                let reply_score = self.search(depth, -INFINITY, -floor);
+                self.engine.undo_move();
+                let Some(reply_score) = reply_score else {
+                    return;
+                };
+                scored_moves.push((*root_move, step_back(-reply_score)));
+                scored_moves.sort_by(|(_, a), (_, b)| b.cmp(a));
+            }
+
+            let mut lines = Vec::with_capacity(line_count);
+            for (root_move, score) in scored_moves.into_iter().take(line_count) {
+                if self.is_stopped() {
+                    return;
+                }
+                let (moves, sans) = self.get_variation(root_move, depth);
+                let evaluation = Evaluation::from_score(score);
+                lines.push(PvLine {
+                    moves,
+                    sans,
+                    evaluation: if turn == GodotPieceColor::White {
+                        evaluation
+                    } else {
+                        evaluation.flipped()
+                    },
+                    depth: line_depth,
+                });
+            }
+
+            // Move ordering for the next depth follows this one
+            let mut ordered_moves: Vec<GodotMove> = lines.iter().map(|line| line.moves[0]).collect();
+            root_moves.retain(|root_move| !ordered_moves.contains(root_move));
+            ordered_moves.append(&mut root_moves);
+            root_moves = ordered_moves;
+
+            let update = MultiPvUpdate {
+                lines,
+                depth: line_depth,
+                nodes: self.nodes,
+                elapsed: self.start_time.elapsed(),
+            };
+            if sender.send((self.generation, update)).is_err() {
+                return;
+            }
+        }
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.latest_generation.load(Ordering::Relaxed) != self.generation
+    }
+
+    /// Alpha-beta from the side to move's point of view, `None` once stopped. The last ply is scored by the bot.
+    fn search(&mut self, depth: u8, mut alpha: i32, beta: i32) -> Option<i32> {
+        if self.is_stopped() {
+            return None;
+        }
+        self.nodes += 1;
+        if depth <= 1 {
+            return Some(match self.engine.search(1) {
+                Some(result) => result.score,
+                None => self.get_game_over_score(),
+            });
+        }
+        let mut moves: Vec<GodotMove> = self.engine.get_legal_moves().into_values().flatten().collect();
+        if moves.is_empty() {
+            return Some(self.get_game_over_score());
+        }
+        // Captures first, as they are the likeliest to cut the search short
+        moves.sort_by_key(|candidate| !self.engine.is_capture(candidate));
+        for candidate in moves {
+            self.engine.play_move(&candidate);
+            let score = self.search(depth - 1, -beta, -alpha);
+            self.engine.undo_move();
+            let score = step_back(-score?);
+            if score >= beta {
+                return Some(score);
+            }
+            alpha = alpha.max(score);
+        }
+        Some(alpha)
+    }
+
+    /// Score of a position without legal moves. Being mated is a ply closer than the mate the bot scores as
+    /// `MATE_SCORE`, so stepping back to the mating move gives exactly that.
+    fn get_game_over_score(&self) -> i32 {
+        if self.engine.is_in_check() { -(MATE_SCORE + 1) } else { 0 }
+    }
+
+    /// `root_move` followed by the bot's choices, as deep as the line was searched
+    fn get_variation(&mut self, root_move: GodotMove, depth: u8) -> (Vec<GodotMove>, Vec<String>) {
+        let mut moves = vec![root_move];
+        let mut sans = vec![self.engine.get_san(&root_move)];
+        self.engine.play_move(&root_move);
+        for remaining_depth in (1..=depth).rev() {
+            let Some(result) = self.engine.search(remaining_depth) else {
+                break;
+            };
+            sans.push(self.engine.get_san(&result.best_move));
+            moves.push(result.best_move);
+            self.engine.play_move(&result.best_move);
+        }
+        for _ in 0..moves.len() {
+            self.engine.undo_move();
+        }
+        (moves, sans)
+    }
+}
+
+/// A score seen from one ply further up the tree, where any mate is a ply further away
+fn step_back(score: i32) -> i32 {
+    let further = score - score.signum();
+    match Evaluation::from_score(further) {
+        Evaluation::MateIn(_) => further,
+        _ => score,
+    }
+}
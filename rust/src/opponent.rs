@@ -0,0 +1,302 @@
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::book::OpeningBook;
+use crate::chess_pieces::GodotPieceColor;
+use crate::consts::UCI_MOVE_TIME_MS;
+use crate::engine::{BackgroundSearch, ChessEngine};
+use crate::fen;
+use crate::moves::GodotMove;
+use crate::tablebase::Tablebases;
+use crate::uci_engine::{GoLimits, UciEngine, UciEvent};
+
+/// Time left on both clocks of a timed game
+pub struct ClockState {
+    pub white_seconds: f64,
+    pub black_seconds: f64,
+    pub increment_seconds: f64,
+}
+
+/// Everything an opponent may need to pick a move
+pub struct MoveRequest<'a> {
+    /// At the position to move in
+    pub engine: &'a ChessEngine,
+    /// `None` for the initial position
+    pub start_fen: Option<&'a str>,
+    /// Moves played from the start position
+    pub moves: &'a [GodotMove],
+    /// `None` in untimed games
+    pub clock: Option<ClockState>,
+}
+
+pub enum OpponentEvent {
+    /// Progress of the search worth showing, like its depth and best line
+    Info(String),
+    /// The chosen move in UCI notation
+    Move(String),
+    /// A move taken from the opening book instead of searched for, in UCI notation
+    BookMove(String),
+}
+
+/// The side of a game played by the computer. Moves are asked for with `request_move` and arrive through `poll`,
+/// so the game keeps running while the opponent thinks.
+pub trait Opponent {
+    fn get_name(&self) -> String;
+
+    fn request_move(&mut self, request: &MoveRequest) -> Result<(), String>;
+
+    /// Events since the last call. An error means the opponent can't go on with the game.
+    fn poll(&mut self) -> Result<Vec<OpponentEvent>, String>;
+
+    /// Drops the move being thought about, e.g. after an undo
+    fn cancel(&mut self);
+}
+
+/// How the bot plays from an opening book
+pub struct BookOptions {
+    /// 0 always plays the book's main line, 1 follows its weights, more spreads the games out
+    pub variety: f64,
+    /// Plies from the start of the game after which the book is left
+    pub max_plies: usize,
+}
+
+/// The `rustier_chess` bot searching to a fixed depth
+pub struct BuiltinBot {
+    book: Option<(OpeningBook, BookOptions)>,
+    depth: u8,
+    /// Events of a move picked without searching, handed out by the next `poll`
+    prepared_events: Vec<OpponentEvent>,
+    /// Picks among the book moves
+    rng: Rng,
+    search: Option<BackgroundSearch>,
+    tablebases: Option<Arc<Tablebases>>,
+}
+
+impl BuiltinBot {
+    pub fn new(depth: u8) -> Self {
+        Self {
+            book: None,
+            depth,
+            prepared_events: Vec::new(),
+            rng: Rng::new(),
+            search: None,
+            tablebases: None,
+        }
+    }
+
+    pub fn with_book(mut self, book: OpeningBook, options: BookOptions) -> Self {
+        self.book = Some((book, options));
+        self
+    }
+
+    /// Plays the tablebases' best move once few enough pieces are left
+    pub fn with_tablebases(mut self, tablebases: Arc<Tablebases>) -> Self {
+        self.tablebases = Some(tablebases);
+        self
+    }
+
+    /// A legal book move for the position, if the game is still short enough to follow the book
+    fn find_book_move(&mut self, request: &MoveRequest) -> Option<String> {
+        let (book, options) = self.book.as_ref()?;
+        if request.moves.len() >= options.max_plies {
+            return None;
+        }
+        let fen = fen::get_fen(request.start_fen, request.engine)?;
+        let book_move = book.pick_move(&fen, options.variety, self.rng.next_unit())?;
+        // Books can be wrong, or hit a different position on a key collision
+        Self::check_legal(request, &book_move.uci)
+    }
+
+    /// The tablebases' move with the result it keeps, if they cover the position
+    fn find_tablebase_move(&self, request: &MoveRequest) -> Option<(String, String)> {
+        let fen = fen::get_fen(request.start_fen, request.engine)?;
+        let probe = self.tablebases.as_ref()?.probe(&fen)?;
+        let best_move = Self::check_legal(request, &probe.best_move?)?;
+        Some((best_move, format!("Tablebase: {}", probe.evaluation)))
+    }
+
+    /// `uci` in the engine's notation, `None` if it isn't legal in the position
+    fn check_legal(request: &MoveRequest, uci: &str) -> Option<String> {
+        request
+            .engine
+            .copy_position()
+            .get_legal_move_from_uci(uci)
+            .map(|legal_move| legal_move.to_uci())
+    }
+}
+
+/// Xorshift64*, plenty for varying the moves of the bots. Seeded from the clock unless given a seed.
+pub struct Rng {
+    state: u64,
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Rng {
+    pub fn new() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos() as u64);
+        Self::with_seed(nanos)
+    }
+
+    /// Any seed works, zero included
+    pub fn with_seed(seed: u64) -> Self {
+        // Mixed so that close seeds don't start out alike, and never zero, where xorshift would stay
+        let state = (seed ^ 0x9E37_79B9_7F4A_7C15).wrapping_mul(0xBF58_476D_1CE4_E5B9) | 1;
+        Self { state }
+    }
+
+    /// Uniform between 0 included and 1 excluded
+    pub fn next_unit(&mut self) -> f64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        let bits = self.state.wrapping_mul(0x2545_F491_4F6C_DD1D);
+        (bits >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// One of `items`, all equally likely, or `None` if there are none
+    pub fn pick<T: Copy>(&mut self, items: &[T]) -> Option<T> {
+        items.get((self.next_unit() * items.len() as f64) as usize).copied()
+    }
+}
+
+impl Opponent for BuiltinBot {
+    fn get_name(&self) -> String {
+        format!("Bot level {}", self.depth)
+    }
+
+    fn request_move(&mut self, request: &MoveRequest) -> Result<(), String> {
+        if let Some(book_move) = self.find_book_move(request) {
+            self.prepared_events = vec![OpponentEvent::BookMove(book_move)];
+        } else if let Some((best_move, info)) = self.find_tablebase_move(request) {
+            self.prepared_events = vec![OpponentEvent::Info(info), OpponentEvent::Move(best_move)];
+        } else {
+            self.search = Some(request.engine.search_in_background(self.depth));
+        }
+        Ok(())
+    }
+
+    fn poll(&mut self) -> Result<Vec<OpponentEvent>, String> {
+        if !self.prepared_events.is_empty() {
+            return Ok(std::mem::take(&mut self.prepared_events));
+        }
+        let Some(result) = self.search.as_ref().and_then(|search| search.try_take()) else {
+            return Ok(Vec::new());
+        };
+        self.search = None;
+        match result {
+            Some(result) => Ok(vec![OpponentEvent::Move(result.best_move.to_uci())]),
+            None => Err("the bot found no move in a position that isn't over".to_string()),
+        }
+    }
+
+    fn cancel(&mut self) {
+        self.prepared_events.clear();
+        self.search = None;
+    }
+}
+
+/// An engine binary speaking UCI
+pub struct UciOpponent {
+    engine: UciEngine,
+    /// File name of the executable, for engines that don't give their name
+    file_name: String,
+}
+
+impl UciOpponent {
+    pub fn launch(path: &str) -> Result<Self, String> {
+        let engine = UciEngine::launch(path, &[]).map_err(|error| error.to_string())?;
+        let file_name = Path::new(path)
+            .file_stem()
+            .map_or(path.to_string(), |stem| stem.to_string_lossy().into_owned());
+        Ok(Self { engine, file_name })
+    }
+}
+
+impl Opponent for UciOpponent {
+    fn get_name(&self) -> String {
+        self.engine.get_name().unwrap_or(self.file_name.as_str()).to_string()
+    }
+
+    fn request_move(&mut self, request: &MoveRequest) -> Result<(), String> {
+        let moves: Vec<String> = request.moves.iter().map(|played_move| played_move.to_uci()).collect();
+        let limits = match &request.clock {
+            Some(clock) => GoLimits::Clock {
+                white: Duration::from_secs_f64(clock.white_seconds),
+                black: Duration::from_secs_f64(clock.black_seconds),
+                white_increment: Duration::from_secs_f64(clock.increment_seconds),
+                black_increment: Duration::from_secs_f64(clock.increment_seconds),
+                white_to_move: request.engine.get_turn() == GodotPieceColor::White,
+            },
+            None => GoLimits::MoveTime(Duration::from_millis(UCI_MOVE_TIME_MS)),
+        };
+        self.engine
+            .go(request.start_fen, &moves, limits)
+            .map_err(|error| error.to_string())
+    }
+
+    fn poll(&mut self) -> Result<Vec<OpponentEvent>, String> {
+        let events = self.engine.poll().map_err(|error| error.to_string())?;
+        events
+            .into_iter()
+            .map(|event| match event {
+                UciEvent::Info(info) => Ok(OpponentEvent::Info(info.to_string())),
+                UciEvent::BestMove(best_move) if best_move == "(none)" || best_move == "0000" => {
+                    Err("the engine found no move in a position that isn't over".to_string())
+                }
+                UciEvent::BestMove(best_move) => Ok(OpponentEvent::Move(best_move)),
+            })
+            .collect()
+    }
+
+    fn cancel(&mut self) {
+        // A failure to send shows up in the next `poll`
+        let _ = self.engine.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Rng;
+
+    #[test]
+    fn same_seed_repeats_the_sequence() {
+        let mut first = Rng::with_seed(42);
+        let mut second = Rng::with_seed(42);
+        for _ in 0..100 {
+            assert_eq!(first.next_unit(), second.next_unit());
+        }
+    }
+
+    #[test]
+    fn close_seeds_differ_from_the_start() {
+        assert_ne!(Rng::with_seed(0).next_unit(), Rng::with_seed(1).next_unit());
+    }
+
+    #[test]
+    fn units_stay_in_range_and_spread_out() {
+        let mut rng = Rng::with_seed(7);
+        let units: Vec<f64> = (0..10_000).map(|_| rng.next_unit()).collect();
+        assert!(units.iter().all(|unit| (0. ..1.).contains(unit)));
+        let mean = units.iter().sum::<f64>() / units.len() as f64;
+        assert!((0.48..0.52).contains(&mean), "mean {}", mean);
+    }
+
+    #[test]
+    fn pick_reaches_every_item() {
+        let mut rng = Rng::with_seed(3);
+        let mut seen = [false; 4];
+        for _ in 0..200 {
+            seen[rng.pick(&[0, 1, 2, 3]).unwrap()] = true;
+        }
+        assert_eq!(seen, [true; 4]);
+        assert_eq!(rng.pick::<u8>(&[]), None);
+    }
+}
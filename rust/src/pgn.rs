@@ -0,0 +1,216 @@
+use crate::chess_pieces::GodotPieceColor;
+
+/// Longest line of movetext, as recommended by the PGN export format
+const MAX_LINE_LENGTH: usize = 79;
+
+struct RecordedMove {
+    san: String,
+    comments: Vec<String>,
+}
+
+/// The moves of one game with their comments, written out as PGN
+pub struct GameRecord {
+    /// Set when the game didn't start from the initial position
+    start_fen: Option<String>,
+    first_turn: GodotPieceColor,
+    first_move_number: u32,
+    moves: Vec<RecordedMove>,
+    /// Comments made before the next move, like hints, end up on that move
+    pending_comments: Vec<String>,
+    result: Option<String>,
+}
+
+impl Default for GameRecord {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl GameRecord {
+    /// Move numbering continues from the side to move and full move number of `start_fen`
+    pub fn new(start_fen: Option<&str>) -> Self {
+        let fields: Vec<&str> = start_fen.map_or(Vec::new(), |fen| fen.split_whitespace().collect());
+        let first_turn = match fields.get(1) {
+            Some(&"b") => GodotPieceColor::Black,
+            _ => GodotPieceColor::White,
+        };
+        let first_move_number = fields
+            .get(5)
+            .and_then(|number| number.parse::<u32>().ok())
+            .unwrap_or(1)
+            .max(1);
+
+        Self {
+            start_fen: start_fen.map(str::to_string),
+            first_turn,
+            first_move_number,
+            moves: Vec::new(),
+            pending_comments: Vec::new(),
+            result: None,
+        }
+    }
+
+    pub fn push_move(&mut self, san: &str) {
+        self.moves.push(RecordedMove {
+            san: san.to_string(),
+            comments: std::mem::take(&mut self.pending_comments),
+        });
+    }
+
+    /// Forgets the last move and its comments, e.g. after an undo
+    pub fn pop_move(&mut self) {
+        self.moves.pop();
+        self.pending_comments.clear();
+        self.result = None;
+    }
+
+    /// Attaches `comment` to the next move played
+    pub fn add_comment(&mut self, comment: &str) {
+        // A closing brace would end the comment early
+        self.pending_comments.push(comment.replace('}', ")"));
+    }
+
+    pub fn set_result(&mut self, result: &str) {
+        self.result = Some(result.to_string());
+    }
+
+    pub fn get_start_fen(&self) -> Option<&str> {
+        self.start_fen.as_deref()
+    }
+
+    pub fn get_result(&self) -> &str {
+        self.result.as_deref().unwrap_or("*")
+    }
+
+    /// Full PGN with `tags` written before the result and setup tags
+    pub fn to_pgn(&self, tags: &[(&str, String)]) -> String {
+        let mut output = String::new();
+        for (name, value) in tags {
+            output.push_str(&format!("[{} \"{}\"]\n", name, value.replace('"', "'")));
+        }
+        output.push_str(&format!("[Result \"{}\"]\n", self.get_result()));
+        if let Some(fen) = &self.start_fen {
+            output.push_str("[SetUp \"1\"]\n");
+            output.push_str(&format!("[FEN \"{}\"]\n", fen));
+        }
+        output.push('\n');
+        output.push_str(&self.get_movetext());
+        output.push('\n');
+        output
+    }
+
+    fn get_movetext(&self) -> String {
+        let mut tokens: Vec<String> = Vec::with_capacity(self.moves.len() * 2 + 1);
+        let mut move_number = self.first_move_number;
+        let mut turn = self.first_turn;
+        // The move number is repeated for black after a comment, and on black's first move
+        let mut needs_number = true;
+        for recorded_move in &self.moves {
+            match turn {
+                GodotPieceColor::White => tokens.push(format!("{}.", move_number)),
+                GodotPieceColor::Black if needs_number => tokens.push(format!("{}...", move_number)),
+                GodotPieceColor::Black => {}
+            }
+            tokens.push(recorded_move.san.clone());
+            for comment in &recorded_move.comments {
+                tokens.push(format!("{{{}}}", comment));
+            }
+            needs_number = !recorded_move.comments.is_empty();
+            if turn == GodotPieceColor::Black {
+                move_number += 1;
+            }
+            turn = turn.opponent_turn();
+        }
+        tokens.push(self.get_result().to_string());
+
+        let mut output = String::new();
+        let mut line_length = 0;
+        for token in tokens {
+            if line_length > 0 && line_length + 1 + token.len() > MAX_LINE_LENGTH {
+                output.push('\n');
+                line_length = 0;
+            } else if line_length > 0 {
+                output.push(' ');
+                line_length += 1;
+            }
+            line_length += token.len();
+            output.push_str(&token);
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record_with_moves(start_fen: Option<&str>, moves: &[&str]) -> GameRecord {
+        let mut record = GameRecord::new(start_fen);
+        for san in moves {
+            record.push_move(san);
+        }
+        record
+    }
+
+    #[test]
+    fn moves_are_numbered_for_white() {
+        let record = record_with_moves(None, &["e4", "e5", "Nf3"]);
+        assert_eq!(record.get_movetext(), "1. e4 e5 2. Nf3 *");
+    }
+
+    #[test]
+    fn black_move_after_comment_repeats_number() {
+        let mut record = GameRecord::new(None);
+        record.add_comment("Hint: e4 +0.30");
+        record.push_move("e4");
+        record.push_move("e5");
+        record.add_comment("Book");
+        record.push_move("Nf3");
+        record.push_move("Nc6");
+        record.set_result("1/2-1/2");
+
+        assert_eq!(
+            record.get_movetext(),
+            "1. e4 {Hint: e4 +0.30} 1... e5 2. Nf3 {Book} 2... Nc6 1/2-1/2"
+        );
+    }
+
+    #[test]
+    fn comments_cannot_close_early() {
+        let mut record = GameRecord::new(None);
+        record.add_comment("a } b");
+        record.push_move("d4");
+        assert_eq!(record.get_movetext(), "1. d4 {a ) b} *");
+    }
+
+    #[test]
+    fn black_first_move_continues_from_fen() {
+        let record = record_with_moves(Some("4k3/8/8/8/8/8/8/4K3 b - - 3 12"), &["Kd7", "Ke2", "Kc6"]);
+        assert_eq!(record.get_movetext(), "12... Kd7 13. Ke2 Kc6 *");
+    }
+
+    #[test]
+    fn undone_move_drops_result() {
+        let mut record = record_with_moves(None, &["f3", "e5", "g4", "Qh4#"]);
+        record.set_result("0-1");
+        record.pop_move();
+        assert_eq!(record.get_movetext(), "1. f3 e5 2. g4 *");
+    }
+
+    #[test]
+    fn long_games_wrap_between_tokens() {
+        let moves = ["Nf3", "Nf6", "Ng1", "Ng8"].repeat(20);
+        let mut record = record_with_moves(None, &moves);
+        record.set_result("1/2-1/2");
+
+        let movetext = record.get_movetext();
+        let lines: Vec<&str> = movetext.lines().collect();
+        assert!(lines.len() > 1);
+        assert!(lines.iter().all(|line| line.len() <= MAX_LINE_LENGTH && !line.ends_with(' ')));
+        // Wrapping only replaces spaces, so nothing is split or lost
+        let unwrapped = lines.join(" ");
+        assert!(unwrapped.starts_with("1. Nf3 Nf6 2. Ng1 Ng8 3. Nf3"));
+        assert!(unwrapped.ends_with("40. Ng1 Ng8 1/2-1/2"));
+        assert_eq!(unwrapped.split(' ').count(), 40 + moves.len() + 1);
+    }
+}
@@ -0,0 +1,147 @@
+use godot::prelude::*;
+
+use crate::engine::ChessEngine;
+use crate::square::GodotSquare;
+use crate::tables;
+
+/// A chess position without any scene nodes, for menus, previews and puzzle validation.
+/// Starts from the initial position, or has no position at all if the engine tables can't be loaded.
+#[derive(GodotClass)]
+#[class(base=RefCounted)]
+pub struct ChessPosition {
+    /// `None` when the engine tables can't be loaded
+    engine: Option<ChessEngine>,
+    base: Base<RefCounted>,
+}
+
+#[godot_api]
+impl IRefCounted for ChessPosition {
+    fn init(base: Base<RefCounted>) -> Self {
+        Self {
+            engine: ChessEngine::new().ok(),
+            base,
+        }
+    }
+}
+
+#[godot_api]
+impl ChessPosition {
+    /// Empty when the engine's move tables load, otherwise why they don't. No game can be played without them.
+    #[func]
+    fn get_engine_error() -> GString {
+        match ChessEngine::check_tables() {
+            Ok(()) => GString::new(),
+            Err(error) => error.into(),
+        }
+    }
+
+    /// Loads the engine tables on a background thread so the first game or FEN check doesn't stall
+    #[func]
+    fn warm_up_engine() {
+        tables::warm_up();
+    }
+
+    /// Like `get_engine_error`, but returns an empty string instead of waiting while the tables are still loading
+    #[func]
+    fn get_finished_engine_error() -> GString {
+        tables::get_finished_load_error().map_or(GString::new(), GString::from)
+    }
+
+    #[func]
+    fn is_valid_fen(fen: String) -> bool {
+        ChessEngine::from_fen(&fen).is_ok()
+    }
+
+    /// Returns false and keeps the current position if the FEN is invalid
+    #[func]
+    fn load_fen(&mut self, fen: String) -> bool {
+        match ChessEngine::from_fen(&fen) {
+            Ok(engine) => {
+                self.engine = Some(engine);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    #[func]
+    fn reset(&mut self) {
+        self.engine = ChessEngine::new().ok();
+    }
+
+    /// UCI strings of all legal moves, sorted
+    #[func]
+    fn get_legal_moves(&mut self) -> PackedStringArray {
+        let Some(engine) = self.engine.as_mut() else {
+            return PackedStringArray::new();
+        };
+        let mut uci_moves: Vec<String> = engine
+            .get_legal_moves()
+            .values()
+            .flatten()
+            .map(|legal_move| legal_move.to_uci())
+            .collect();
+        uci_moves.sort();
+        uci_moves.iter().map(|uci| GString::from(uci.as_str())).collect()
+    }
+
+    /// Returns false if the move isn't legal
+    #[func]
+    fn make_move(&mut self, uci: String) -> bool {
+        let Some(engine) = self.engine.as_mut() else {
+            return false;
+        };
+        match engine.get_legal_move_from_uci(&uci) {
+            Some(legal_move) => {
+                engine.play_move(&legal_move);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns false if no move has been made
+    #[func]
+    fn unmake_move(&mut self) -> bool {
+        self.engine.as_mut().is_some_and(ChessEngine::undo_move)
+    }
+
+    #[func]
+    fn get_turn(&self) -> String {
+        self.engine
+            .as_ref()
+            .map_or(String::new(), |engine| engine.get_turn().name().into())
+    }
+
+    /// FEN letter of the piece on `square`, upper case for white, or an empty string
+    #[func]
+    fn get_piece_at(&self, square: String) -> String {
+        GodotSquare::from_name(&square)
+            .and_then(|square| self.engine.as_ref()?.get_piece_on_square(&square))
+            .map_or(String::new(), |(color, kind)| kind.get_fen_char(color).to_string())
+    }
+
+    #[func]
+    fn is_check(&self) -> bool {
+        self.engine.as_ref().is_some_and(ChessEngine::is_in_check)
+    }
+
+    #[func]
+    fn is_checkmate(&mut self) -> bool {
+        self.engine
+            .as_mut()
+            .is_some_and(|engine| engine.is_in_check() && engine.get_legal_moves().is_empty())
+    }
+
+    #[func]
+    fn is_stalemate(&mut self) -> bool {
+        self.engine
+            .as_mut()
+            .is_some_and(|engine| !engine.is_in_check() && engine.get_legal_moves().is_empty())
+    }
+
+    #[func]
+    fn perft(&mut self, depth: u32) -> i64 {
+        self.engine.as_mut().map_or(0, |engine| engine.perft(depth) as i64)
+    }
+}
@@ -0,0 +1,145 @@
+use godot::classes::ConfigFile;
+use godot::global::Error;
+use godot::prelude::*;
+
+use crate::consts::{DEFAULT_BOOK_FILE_PATH, DEFAULT_BOT_LEVEL, DEFAULT_THEME, SETTINGS_FILE_PATH, SETTINGS_SECTION};
+
+/// User settings persisted in `user://settings.cfg`. Missing or malformed keys fall back to the defaults.
+#[derive(GodotClass)]
+#[class(init, base=RefCounted)]
+pub struct GodotSettings {
+    #[var]
+    #[init(val = GString::from(DEFAULT_THEME))]
+    pub theme: GString,
+    /// Linear volume between 0 and 1
+    #[var]
+    #[init(val = 1.)]
+    pub sound_volume: f32,
+    #[var]
+    pub muted: bool,
+    /// Multiplier of the default animation speed, 0 disables animations
+    #[var]
+    #[init(val = 1.)]
+    pub animation_speed: f32,
+    #[var]
+    #[init(val = true)]
+    pub show_legal_moves: bool,
+    #[var]
+    pub auto_queen: bool,
+    #[var]
+    #[init(val = DEFAULT_BOT_LEVEL)]
+    pub bot_level: i32,
+    /// Chance between 0 and 1 that the blundering bot throws a move away
+    #[var]
+    #[init(val = 0.25)]
+    pub blunder_chance: f32,
+    /// `minutes+increment`, e.g. `5+3`, or `none`
+    #[var]
+    #[init(val = GString::from("none"))]
+    pub time_control: GString,
+    /// Hints allowed per game, negative for unlimited
+    #[var]
+    #[init(val = 3)]
+    pub hint_limit: i32,
+    /// Never shown in rated games
+    #[var]
+    #[init(val = true)]
+    pub show_eval_bar: bool,
+    /// Executable of the UCI engine to play against, empty when none is set up
+    #[var]
+    pub uci_engine_path: GString,
+    /// Polyglot opening book of the bot, in `res://` or `user://`
+    #[var]
+    #[init(val = GString::from(DEFAULT_BOOK_FILE_PATH))]
+    pub book_path: GString,
+    /// Spread of the book moves the bot picks, 0 for always the main line
+    #[var]
+    #[init(val = 1.)]
+    pub book_variety: f32,
+    /// Plies the bot plays from the book, 0 turns the book off
+    #[var]
+    #[init(val = 16)]
+    pub book_max_plies: i32,
+    /// Folder with Syzygy tablebases, empty when there are none
+    #[var]
+    pub syzygy_path: GString,
+    /// Ends games as soon as the tablebases know the result
+    #[var]
+    pub tablebase_adjudication: bool,
+    base: Base<RefCounted>,
+}
+
+#[godot_api]
+impl GodotSettings {
+    #[func]
+    pub fn load_settings() -> Gd<Self> {
+        let mut settings = Self::new_gd();
+        let mut config = ConfigFile::new_gd();
+        if config.load(SETTINGS_FILE_PATH) != Error::OK {
+            return settings;
+        }
+
+        {
+            let mut settings_mut = settings.bind_mut();
+            settings_mut.theme = Self::read(&config, "theme", settings_mut.theme.clone());
+            settings_mut.sound_volume = Self::read(&config, "sound_volume", settings_mut.sound_volume).clamp(0., 1.);
+            settings_mut.muted = Self::read(&config, "muted", settings_mut.muted);
+            settings_mut.animation_speed = Self::read(&config, "animation_speed", settings_mut.animation_speed).max(0.);
+            settings_mut.show_legal_moves = Self::read(&config, "show_legal_moves", settings_mut.show_legal_moves);
+            settings_mut.auto_queen = Self::read(&config, "auto_queen", settings_mut.auto_queen);
+            settings_mut.bot_level = Self::read(&config, "bot_level", settings_mut.bot_level);
+            settings_mut.blunder_chance = Self::read(&config, "blunder_chance", settings_mut.blunder_chance).clamp(0., 1.);
+            settings_mut.time_control = Self::read(&config, "time_control", settings_mut.time_control.clone());
+            settings_mut.hint_limit = Self::read(&config, "hint_limit", settings_mut.hint_limit);
+            settings_mut.show_eval_bar = Self::read(&config, "show_eval_bar", settings_mut.show_eval_bar);
+            settings_mut.uci_engine_path = Self::read(&config, "uci_engine_path", settings_mut.uci_engine_path.clone());
+            settings_mut.book_path = Self::read(&config, "book_path", settings_mut.book_path.clone());
+            settings_mut.book_variety = Self::read(&config, "book_variety", settings_mut.book_variety).max(0.);
+            settings_mut.book_max_plies = Self::read(&config, "book_max_plies", settings_mut.book_max_plies).max(0);
+            settings_mut.syzygy_path = Self::read(&config, "syzygy_path", settings_mut.syzygy_path.clone());
+            settings_mut.tablebase_adjudication =
+                Self::read(&config, "tablebase_adjudication", settings_mut.tablebase_adjudication);
+        }
+        settings
+    }
+
+    #[func]
+    pub fn save_settings(&self) -> bool {
+        let mut config = ConfigFile::new_gd();
+        config.set_value(SETTINGS_SECTION, "theme", &self.theme.to_variant());
+        config.set_value(SETTINGS_SECTION, "sound_volume", &self.sound_volume.to_variant());
+        config.set_value(SETTINGS_SECTION, "muted", &self.muted.to_variant());
+        config.set_value(SETTINGS_SECTION, "animation_speed", &self.animation_speed.to_variant());
+        config.set_value(SETTINGS_SECTION, "show_legal_moves", &self.show_legal_moves.to_variant());
+        config.set_value(SETTINGS_SECTION, "auto_queen", &self.auto_queen.to_variant());
+        config.set_value(SETTINGS_SECTION, "bot_level", &self.bot_level.to_variant());
+        config.set_value(SETTINGS_SECTION, "blunder_chance", &self.blunder_chance.to_variant());
+        config.set_value(SETTINGS_SECTION, "time_control", &self.time_control.to_variant());
+        config.set_value(SETTINGS_SECTION, "hint_limit", &self.hint_limit.to_variant());
+        config.set_value(SETTINGS_SECTION, "show_eval_bar", &self.show_eval_bar.to_variant());
+        config.set_value(SETTINGS_SECTION, "uci_engine_path", &self.uci_engine_path.to_variant());
+        config.set_value(SETTINGS_SECTION, "book_path", &self.book_path.to_variant());
+        config.set_value(SETTINGS_SECTION, "book_variety", &self.book_variety.to_variant());
+        config.set_value(SETTINGS_SECTION, "book_max_plies", &self.book_max_plies.to_variant());
+        config.set_value(SETTINGS_SECTION, "syzygy_path", &self.syzygy_path.to_variant());
+        config.set_value(
+            SETTINGS_SECTION,
+            "tablebase_adjudication",
+            &self.tablebase_adjudication.to_variant(),
+        );
+
+        let result = config.save(SETTINGS_FILE_PATH);
+        if result != Error::OK {
+            godot_error!("Failed to save settings to {}: {:?}", SETTINGS_FILE_PATH, result);
+        }
+        result == Error::OK
+    }
+
+    fn read<T: FromGodot + ToGodot>(config: &Gd<ConfigFile>, key: &str, default: T) -> T {
+        let value = config
+            .get_value_ex(SETTINGS_SECTION, key)
+            .default(&default.to_variant())
+            .done();
+        value.try_to::<T>().unwrap_or(default)
+    }
+}
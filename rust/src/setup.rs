@@ -0,0 +1,343 @@
+use crate::chess_pieces::{GodotPieceColor, GodotPieceKind};
+use crate::square::GodotSquare;
+
+pub type SetupPiece = (GodotPieceColor, GodotPieceKind);
+
+/// Castling right letters in the order of `PositionSetup::castling_rights`
+const CASTLING_LETTERS: [char; 4] = ['K', 'Q', 'k', 'q'];
+/// Color, king file and rook file of each castling right, on the color's first rank
+const CASTLING_SQUARES: [(GodotPieceColor, usize, usize); 4] = [
+    (GodotPieceColor::White, 4, 7),
+    (GodotPieceColor::White, 4, 0),
+    (GodotPieceColor::Black, 4, 7),
+    (GodotPieceColor::Black, 4, 0),
+];
+const KNIGHT_STEPS: [(isize, isize); 8] = [(1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2)];
+/// King steps, also the directions sliding pieces move in
+const KING_STEPS: [(isize, isize); 8] = [(0, 1), (1, 1), (1, 0), (1, -1), (0, -1), (-1, -1), (-1, 0), (-1, 1)];
+
+/// A position being set up by hand, which unlike a played one may not be legal yet
+#[derive(Clone, PartialEq, Debug)]
+pub struct PositionSetup {
+    /// Indexed by rank, then file, White's first rank first
+    pub squares: [[Option<SetupPiece>; 8]; 8],
+    pub side_to_move: GodotPieceColor,
+    /// White short, white long, black short, black long
+    pub castling_rights: [bool; 4],
+    pub en_passant: Option<GodotSquare>,
+}
+
+impl Default for PositionSetup {
+    fn default() -> Self {
+        Self {
+            squares: [[None; 8]; 8],
+            side_to_move: GodotPieceColor::White,
+            castling_rights: [false; 4],
+            en_passant: None,
+        }
+    }
+}
+
+impl PositionSetup {
+    pub fn get_piece(&self, square: &GodotSquare) -> Option<SetupPiece> {
+        self.squares[square.get_rank() as usize][square.get_file() as usize]
+    }
+
+    pub fn set_piece(&mut self, square: &GodotSquare, piece: Option<SetupPiece>) {
+        self.squares[square.get_rank() as usize][square.get_file() as usize] = piece;
+    }
+
+    /// Takes the FEN castling field, e.g. `KQkq`, `Kq` or `-`
+    pub fn set_castling_rights(&mut self, rights: &str) {
+        self.castling_rights = CASTLING_LETTERS.map(|right| rights.contains(right));
+    }
+
+    /// The FEN castling field
+    pub fn get_castling_rights(&self) -> String {
+        let rights: String = CASTLING_LETTERS
+            .into_iter()
+            .zip(self.castling_rights)
+            .filter_map(|(right, enabled)| enabled.then_some(right))
+            .collect();
+        if rights.is_empty() { "-".into() } else { rights }
+    }
+
+    pub fn get_fen(&self) -> String {
+        self.get_fen_with_turn(self.side_to_move, self.en_passant)
+    }
+
+    /// `None` for a legal position, otherwise a message describing the first problem found
+    pub fn find_problem(&self) -> Option<String> {
+        for color in [GodotPieceColor::White, GodotPieceColor::Black] {
+            let n_kings = self.count_pieces((color, GodotPieceKind::King));
+            if n_kings != 1 {
+                return Some(format!("{} needs exactly one king", color.name()));
+            }
+        }
+
+        let pawn_on_back_rank = [self.squares[0], self.squares[7]]
+            .iter()
+            .flatten()
+            .any(|piece| matches!(piece, Some((_, GodotPieceKind::Pawn))));
+        if pawn_on_back_rank {
+            return Some("Pawns can't stand on the first or last rank".into());
+        }
+
+        for (enabled, (color, king_file, rook_file)) in self.castling_rights.into_iter().zip(CASTLING_SQUARES) {
+            let rank = &self.squares[Self::get_first_rank(color)];
+            if enabled
+                && (rank[king_file] != Some((color, GodotPieceKind::King))
+                    || rank[rook_file] != Some((color, GodotPieceKind::Rook)))
+            {
+                return Some("Castling rights need the king and rook on their starting squares".into());
+            }
+        }
+
+        if let Some(en_passant) = self.en_passant.filter(|en_passant| !self.is_en_passant_possible(en_passant)) {
+            return Some(format!("{} can't be an en passant square in this position", en_passant));
+        }
+
+        // The side that just moved can't have left its king in check
+        let waiting_side = self.side_to_move.opponent_turn();
+        if self.is_in_check(waiting_side) {
+            return Some(format!("{} is in check but it's not their move", waiting_side.name()));
+        }
+        None
+    }
+
+    fn get_first_rank(color: GodotPieceColor) -> usize {
+        match color {
+            GodotPieceColor::White => 0,
+            GodotPieceColor::Black => 7,
+        }
+    }
+
+    fn count_pieces(&self, piece: SetupPiece) -> usize {
+        self.squares.iter().flatten().filter(|square| **square == Some(piece)).count()
+    }
+
+    fn is_en_passant_possible(&self, en_passant: &GodotSquare) -> bool {
+        // Rank of the en passant square, and the direction the double-stepped pawn came from
+        let (expected_rank, forward) = match self.side_to_move {
+            GodotPieceColor::White => (5, 1),
+            GodotPieceColor::Black => (2, -1),
+        };
+        let (rank, file) = (en_passant.get_rank() as usize, en_passant.get_file() as usize);
+        if rank != expected_rank {
+            return false;
+        }
+
+        let pawn_rank = rank.wrapping_add_signed(-forward);
+        let origin_rank = rank.wrapping_add_signed(forward);
+        self.squares[rank][file].is_none()
+            && self.squares[origin_rank][file].is_none()
+            && self.squares[pawn_rank][file] == Some((self.side_to_move.opponent_turn(), GodotPieceKind::Pawn))
+    }
+
+    fn is_in_check(&self, color: GodotPieceColor) -> bool {
+        let king = Some((color, GodotPieceKind::King));
+        (0..8)
+            .flat_map(|rank| (0..8).map(move |file| (rank, file)))
+            .filter(|(rank, file)| self.squares[*rank][*file] == king)
+            .any(|(rank, file)| self.is_attacked(rank as isize, file as isize, color.opponent_turn()))
+    }
+
+    fn is_attacked(&self, rank: isize, file: isize, attacker: GodotPieceColor) -> bool {
+        let get_piece = |rank: isize, file: isize| -> Option<SetupPiece> {
+            if (0..8).contains(&rank) && (0..8).contains(&file) {
+                self.squares[rank as usize][file as usize]
+            } else {
+                None
+            }
+        };
+        let is_attacker = |piece: Option<SetupPiece>, kinds: &[GodotPieceKind]| matches!(piece, Some((color, kind)) if color == attacker && kinds.contains(&kind));
+
+        // Pawns attack forward, so an attacking pawn stands a rank behind from its own side
+        let pawn_rank = match attacker {
+            GodotPieceColor::White => rank - 1,
+            GodotPieceColor::Black => rank + 1,
+        };
+        if [-1, 1]
+            .into_iter()
+            .any(|step| is_attacker(get_piece(pawn_rank, file + step), &[GodotPieceKind::Pawn]))
+        {
+            return true;
+        }
+        let is_stepper_attack = |steps: &[(isize, isize)], kind: GodotPieceKind| {
+            steps
+                .iter()
+                .any(|(rank_step, file_step)| is_attacker(get_piece(rank + rank_step, file + file_step), &[kind]))
+        };
+        if is_stepper_attack(&KNIGHT_STEPS, GodotPieceKind::Knight) || is_stepper_attack(&KING_STEPS, GodotPieceKind::King) {
+            return true;
+        }
+
+        KING_STEPS.into_iter().any(|(rank_step, file_step)| {
+            let sliders = if rank_step == 0 || file_step == 0 {
+                [GodotPieceKind::Rook, GodotPieceKind::Queen]
+            } else {
+                [GodotPieceKind::Bishop, GodotPieceKind::Queen]
+            };
+            // The first piece in the direction blocks the rest
+            let (mut ray_rank, mut ray_file) = (rank + rank_step, file + file_step);
+            while (0..8).contains(&ray_rank) && (0..8).contains(&ray_file) {
+                if let Some(piece) = self.squares[ray_rank as usize][ray_file as usize] {
+                    return is_attacker(Some(piece), &sliders);
+                }
+                ray_rank += rank_step;
+                ray_file += file_step;
+            }
+            false
+        })
+    }
+
+    fn get_fen_with_turn(&self, turn: GodotPieceColor, en_passant: Option<GodotSquare>) -> String {
+        let mut placement = String::with_capacity(72);
+        for rank in (0..8).rev() {
+            let mut n_empty = 0;
+            for piece in self.squares[rank] {
+                match piece {
+                    Some((color, kind)) => {
+                        if n_empty > 0 {
+                            placement.push_str(&n_empty.to_string());
+                            n_empty = 0;
+                        }
+                        placement.push(kind.get_fen_char(color));
+                    }
+                    None => n_empty += 1,
+                }
+            }
+            if n_empty > 0 {
+                placement.push_str(&n_empty.to_string());
+            }
+            if rank > 0 {
+                placement.push('/');
+            }
+        }
+
+        let en_passant = en_passant.map_or("-".to_string(), |square| square.to_string());
+        format!("{} {} {} {} 0 1", placement, turn, self.get_castling_rights(), en_passant)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PositionSetup, SetupPiece};
+    use crate::chess_pieces::GodotPieceColor::{self, Black, White};
+    use crate::chess_pieces::GodotPieceKind::{self, Bishop, King, Knight, Pawn, Queen, Rook};
+    use crate::square::GodotSquare;
+
+    fn piece(color: GodotPieceColor, kind: GodotPieceKind) -> Option<SetupPiece> {
+        Some((color, kind))
+    }
+
+    /// Kings on e1 and e8 plus `pieces`, White to move, no castling rights
+    fn setup_with(pieces: &[(&str, GodotPieceColor, GodotPieceKind)]) -> PositionSetup {
+        let mut setup = PositionSetup::default();
+        for (name, color, kind) in [("e1", White, King), ("e8", Black, King)].iter().chain(pieces) {
+            setup.set_piece(&GodotSquare::from_name(name).unwrap(), piece(*color, *kind));
+        }
+        setup
+    }
+
+    fn start_position() -> PositionSetup {
+        let back_rank = [Rook, Knight, Bishop, Queen, King, Bishop, Knight, Rook];
+        let mut setup = PositionSetup::default();
+        setup.squares[0] = back_rank.map(|kind| piece(White, kind));
+        setup.squares[1] = [piece(White, Pawn); 8];
+        setup.squares[6] = [piece(Black, Pawn); 8];
+        setup.squares[7] = back_rank.map(|kind| piece(Black, kind));
+        setup.set_castling_rights("KQkq");
+        setup
+    }
+
+    #[test]
+    fn start_position_is_legal() {
+        let setup = start_position();
+        assert_eq!(setup.find_problem(), None);
+        assert_eq!(setup.get_fen(), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+    }
+
+    #[test]
+    fn each_side_needs_one_king() {
+        let mut setup = setup_with(&[]);
+        setup.set_piece(&GodotSquare::from_name("e8").unwrap(), None);
+        assert_eq!(setup.find_problem().unwrap(), "black needs exactly one king");
+        let setup = setup_with(&[("a1", White, King)]);
+        assert_eq!(setup.find_problem().unwrap(), "white needs exactly one king");
+    }
+
+    #[test]
+    fn pawns_stay_off_the_back_ranks() {
+        for name in ["a1", "h8"] {
+            let setup = setup_with(&[(name, Black, Pawn)]);
+            assert_eq!(setup.find_problem().unwrap(), "Pawns can't stand on the first or last rank");
+        }
+        assert_eq!(setup_with(&[("a2", Black, Pawn), ("h7", White, Pawn)]).find_problem(), None);
+    }
+
+    #[test]
+    fn side_not_to_move_cannot_be_in_check() {
+        for attacker in [
+            ("e4", Black, Rook),
+            ("a5", Black, Bishop),
+            ("d3", Black, Knight),
+            ("d2", Black, Pawn),
+        ] {
+            let mut setup = setup_with(&[attacker]);
+            assert_eq!(setup.find_problem(), None, "{:?}", attacker);
+            setup.side_to_move = Black;
+            assert_eq!(
+                setup.find_problem().unwrap(),
+                "white is in check but it's not their move",
+                "{:?}",
+                attacker
+            );
+        }
+    }
+
+    #[test]
+    fn blocked_attacks_give_no_check() {
+        let mut setup = setup_with(&[
+            ("a8", White, Rook),
+            ("c8", Black, Knight),
+            ("a4", White, Bishop),
+            ("c6", White, Pawn),
+        ]);
+        setup.side_to_move = Black;
+        assert_eq!(setup.find_problem(), None);
+        setup.side_to_move = White;
+        setup.set_piece(&GodotSquare::from_name("c6").unwrap(), None);
+        assert_eq!(setup.find_problem().unwrap(), "black is in check but it's not their move");
+    }
+
+    #[test]
+    fn castling_rights_need_the_king_and_rook_at_home() {
+        let mut setup = start_position();
+        setup.set_piece(&GodotSquare::from_name("h8").unwrap(), None);
+        assert_eq!(
+            setup.find_problem().unwrap(),
+            "Castling rights need the king and rook on their starting squares"
+        );
+        setup.set_castling_rights("KQq");
+        assert_eq!(setup.find_problem(), None);
+        assert_eq!(setup.get_castling_rights(), "KQq");
+    }
+
+    #[test]
+    fn en_passant_needs_a_pawn_that_just_double_stepped() {
+        let mut setup = setup_with(&[("d5", Black, Pawn), ("e5", White, Pawn)]);
+        setup.en_passant = GodotSquare::from_name("d6");
+        assert_eq!(setup.find_problem(), None);
+        assert_eq!(setup.get_fen(), "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1");
+
+        for name in ["c6", "d3"] {
+            setup.en_passant = GodotSquare::from_name(name);
+            let problem = format!("{} can't be an en passant square in this position", name);
+            assert_eq!(setup.find_problem(), Some(problem));
+        }
+        setup.en_passant = GodotSquare::from_name("d6");
+        setup.side_to_move = Black;
+        assert!(setup.find_problem().is_some());
+    }
+}
@@ -1,34 +1,155 @@
-use godot::classes::{AudioStream, AudioStreamPlayer};
+use std::collections::HashMap;
+
+use godot::classes::{AudioStream, AudioStreamMp3, AudioStreamOggVorbis, AudioStreamPlayer, FileAccess, ResourceLoader};
+use godot::global::linear_to_db;
 use godot::obj::{Gd, NewAlloc, NewGd};
-use godot::prelude::GodotClass;
-use godot::tools::load;
-
-#[derive(GodotClass)]
-#[class(no_init, base=AudioStreamPlayer)]
-pub struct GodotSounds {
-    #[allow(dead_code)]
-    stream: Gd<AudioStream>,
-    pub player: Gd<AudioStreamPlayer>,
+use godot::tools::try_load;
+
+use crate::consts::*;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum SoundEvent {
+    Move,
+    Capture,
+    Check,
+    Castle,
+    Promote,
+    IllegalMove,
+    GameStart,
+    GameEnd,
+    LowTime,
+}
+
+impl SoundEvent {
+    pub const ALL: [Self; 9] = [
+        Self::Move,
+        Self::Capture,
+        Self::Check,
+        Self::Castle,
+        Self::Promote,
+        Self::IllegalMove,
+        Self::GameStart,
+        Self::GameEnd,
+        Self::LowTime,
+    ];
+
+    fn file_name(&self) -> &'static str {
+        match self {
+            Self::Move => MOVE_SOUND_FILE_NAME,
+            Self::Capture => CAPTURE_SOUND_FILE_NAME,
+            Self::Check => CHECK_SOUND_FILE_NAME,
+            Self::Castle => CASTLE_SOUND_FILE_NAME,
+            Self::Promote => PROMOTE_SOUND_FILE_NAME,
+            Self::IllegalMove => ILLEGAL_MOVE_SOUND_FILE_NAME,
+            Self::GameStart => GAME_START_SOUND_FILE_NAME,
+            Self::GameEnd => GAME_END_SOUND_FILE_NAME,
+            Self::LowTime => LOW_TIME_SOUND_FILE_NAME,
+        }
+    }
+
+    /// Event whose sound is used when neither the sound pack nor the bundled resources have one for this event, e.g.
+    /// when a build leaves some sounds out
+    fn fallback(&self) -> Option<Self> {
+        match self {
+            Self::Move => None,
+            Self::Capture | Self::Check | Self::Castle | Self::Promote | Self::GameStart | Self::LowTime => Some(Self::Move),
+            Self::GameEnd | Self::IllegalMove => Some(Self::Capture),
+        }
+    }
+
+    /// Pitch scale for a sound borrowed from the fallback, so events sharing one still sound different
+    fn fallback_pitch(&self) -> f32 {
+        match self {
+            Self::Move | Self::Capture => 1.,
+            Self::Check => 1.25,
+            Self::Castle => 0.85,
+            Self::Promote => 1.5,
+            Self::IllegalMove => 0.6,
+            Self::GameStart => 1.15,
+            Self::GameEnd => 0.75,
+            Self::LowTime => 1.8,
+        }
+    }
+}
+
+/// One player per sound event. A file in `user://sounds/` named like the bundled one overrides it. The players are
+/// only freed with the scene tree, so the game adds every one of them as its child.
+pub struct SoundBank {
+    players: HashMap<SoundEvent, Gd<AudioStreamPlayer>>,
 }
 
-impl GodotSounds {
-    pub fn empty() -> Gd<Self> {
-        Gd::from_object(Self {
-            stream: AudioStream::new_gd(),
-            player: AudioStreamPlayer::new_alloc(),
-        })
+impl SoundBank {
+    pub fn new() -> Self {
+        Self {
+            players: HashMap::with_capacity(SoundEvent::ALL.len()),
+        }
     }
 
-    pub fn from_path(path: &str) -> Gd<Self> {
-        let stream: Gd<AudioStream> = load(path);
-        let mut player = AudioStreamPlayer::new_alloc();
-        player.set_stream(&stream);
+    pub fn load() -> Self {
+        let mut players = HashMap::with_capacity(SoundEvent::ALL.len());
+        for event in SoundEvent::ALL {
+            let mut player = AudioStreamPlayer::new_alloc();
+            if let Some(stream) = Self::load_stream(event) {
+                player.set_stream(&stream);
+            } else if let Some(stream) = event.fallback().and_then(Self::load_stream_or_fallback) {
+                player.set_stream(&stream);
+                player.set_pitch_scale(event.fallback_pitch());
+            }
+            players.insert(event, player);
+        }
+        Self { players }
+    }
+
+    fn load_stream(event: SoundEvent) -> Option<Gd<AudioStream>> {
+        if let Some(stream) = Self::load_user_stream(event.file_name()) {
+            return Some(stream);
+        }
+
+        // The bundled sounds are Ogg Vorbis or WAV, whichever they were made as
+        let ogg_path = format!("{}{}{}", RESOURCES_FOLDER_PATH, SOUNDS_SUBFOLDER_PATH, event.file_name());
+        let wav_path = ogg_path.replace(".ogg", ".wav");
+        [ogg_path, wav_path]
+            .iter()
+            .filter(|path| ResourceLoader::singleton().exists(*path))
+            .find_map(|path| try_load::<AudioStream>(path).ok())
+    }
+
+    fn load_stream_or_fallback(event: SoundEvent) -> Option<Gd<AudioStream>> {
+        Self::load_stream(event).or_else(|| event.fallback().and_then(Self::load_stream_or_fallback))
+    }
+
+    /// Sound pack files aren't imported by the editor, so they are decoded directly. Both Ogg Vorbis and MP3 work.
+    fn load_user_stream(file_name: &str) -> Option<Gd<AudioStream>> {
+        let ogg_path = format!("{}{}", USER_SOUND_PACK_FOLDER_PATH, file_name);
+        if FileAccess::file_exists(&ogg_path) {
+            return AudioStreamOggVorbis::load_from_file(&ogg_path).map(|stream| stream.upcast());
+        }
+
+        let mp3_path = ogg_path.replace(".ogg", ".mp3");
+        if FileAccess::file_exists(&mp3_path) {
+            let mut stream = AudioStreamMp3::new_gd();
+            stream.set_data(&FileAccess::get_file_as_bytes(&mp3_path));
+            return Some(stream.upcast());
+        }
+
+        None
+    }
+
+    pub fn get_players(&self) -> Vec<Gd<AudioStreamPlayer>> {
+        self.players.values().cloned().collect()
+    }
 
-        Gd::from_object(Self { stream, player })
+    pub fn play(&mut self, event: SoundEvent) {
+        if let Some(player) = self.players.get_mut(&event) {
+            player.play();
+        }
     }
 
-    #[allow(dead_code)]
-    pub fn play(&mut self) {
-        self.player.play();
+    /// Linear volume between 0 and 1
+    pub fn set_volume(&mut self, volume: f32) {
+        let volume_db = linear_to_db(volume as f64) as f32;
+        for player in self.players.values_mut() {
+            player.set_volume_db(volume_db);
+        }
     }
 }
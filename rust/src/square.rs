@@ -1,5 +1,6 @@
 use std::fmt::Display;
 
+#[cfg(feature = "godot")]
 use godot::builtin::Vector2;
 use rustier_chess::types::square::Square;
 
@@ -37,6 +38,7 @@ impl GodotSquare {
         Self::from_u8((i % 8) as u8, (i / 8) as u8, player_color)
     }
 
+    #[cfg(feature = "godot")]
     pub fn from_ui_vector2(vec: Vector2, square_size: f32, player_color: &GodotPieceColor) -> Self {
         Self::from_u8((vec.x / square_size) as u8, (vec.y / square_size) as u8, player_color)
     }
@@ -45,6 +47,18 @@ impl GodotSquare {
         Self(square)
     }
 
+    /// Parses algebraic square names like `e4`
+    pub fn from_name(name: &str) -> Option<Self> {
+        let &[file, rank] = name.as_bytes() else {
+            return None;
+        };
+        if !(b'a'..=b'h').contains(&file) || !(b'1'..=b'8').contains(&rank) {
+            return None;
+        }
+        Some(Self(Square::new((rank - b'1') * 8 + file - b'a')))
+    }
+
+    #[cfg(feature = "godot")]
     pub fn get_ui_vector2(&self, square_size: f32, player_color: &GodotPieceColor) -> Vector2 {
         let square_index = self.get_field_index(player_color);
         Vector2::new(
@@ -72,6 +86,11 @@ impl GodotSquare {
     pub fn get_square(&self) -> Square {
         self.0
     }
+
+    /// Engine square index, a1 = 0 to h8 = 63
+    pub fn get_index(&self) -> usize {
+        (self.0.get_rank() * 8 + self.0.get_file()) as usize
+    }
 }
 
 impl Display for GodotSquare {
@@ -0,0 +1,86 @@
+#[cfg(feature = "syzygy")]
+use shakmaty::fen::Fen;
+#[cfg(feature = "syzygy")]
+use shakmaty::{CastlingMode, Chess};
+#[cfg(feature = "syzygy")]
+use shakmaty_syzygy::{Tablebase, Wdl};
+
+use crate::engine::Evaluation;
+
+/// What the tablebases know about a position
+pub struct TablebaseProbe {
+    /// From the side to move's point of view, `TablebaseWin` or `TablebaseDraw`
+    pub evaluation: Evaluation,
+    /// A move that keeps the result in UCI notation, `None` when the game is over
+    pub best_move: Option<String>,
+}
+
+/// Syzygy WDL and DTZ tables read through `shakmaty-syzygy`. Without the `syzygy` feature they can't be opened, so
+/// every caller falls back to searching.
+pub struct Tablebases {
+    #[cfg(feature = "syzygy")]
+    tables: Tablebase<Chess>,
+}
+
+impl Tablebases {
+    /// Indexes the tables in `folder`, failing if there are none
+    #[cfg(feature = "syzygy")]
+    pub fn open(folder: &str) -> Result<Self, String> {
+        let mut tables = Tablebase::new();
+        match tables.add_directory(folder) {
+            Ok(0) => Err(format!("no Syzygy tables in {}", folder)),
+            Ok(_) => Ok(Self { tables }),
+            Err(error) => Err(format!("can't read {}: {}", folder, error)),
+        }
+    }
+
+    #[cfg(not(feature = "syzygy"))]
+    pub fn open(_folder: &str) -> Result<Self, String> {
+        Err("built without the syzygy feature".to_string())
+    }
+
+    /// Most pieces, kings included, of the positions the tables cover
+    #[cfg(feature = "syzygy")]
+    pub fn get_max_pieces(&self) -> usize {
+        self.tables.max_pieces()
+    }
+
+    #[cfg(not(feature = "syzygy"))]
+    pub fn get_max_pieces(&self) -> usize {
+        0
+    }
+
+    /// `None` for positions with more pieces than the tables cover, with castling rights, or whose table is missing
+    pub fn probe(&self, fen: &str) -> Option<TablebaseProbe> {
+        let placement = fen.split_whitespace().next()?;
+        let piece_count = placement.chars().filter(char::is_ascii_alphabetic).count();
+        if piece_count > self.get_max_pieces() {
+            return None;
+        }
+        self.probe_tables(fen)
+    }
+
+    #[cfg(feature = "syzygy")]
+    fn probe_tables(&self, fen: &str) -> Option<TablebaseProbe> {
+        let position: Chess = fen.parse::<Fen>().ok()?.into_position(CastlingMode::Standard).ok()?;
+        // Cursed wins and blessed losses are draws under the fifty-move rule
+        let wdl = self.tables.probe_wdl_after_zeroing(&position).ok()?;
+        let plies = self.tables.probe_dtz(&position).ok()?.ignore_rounding().0;
+        let moves = (plies.abs() + 1) / 2;
+        let evaluation = match wdl {
+            Wdl::Win => Evaluation::TablebaseWin(moves),
+            Wdl::Loss => Evaluation::TablebaseWin(-moves),
+            Wdl::CursedWin | Wdl::Draw | Wdl::BlessedLoss => Evaluation::TablebaseDraw,
+        };
+        let best_move = self.tables.best_move(&position).ok()?;
+        Some(TablebaseProbe {
+            evaluation,
+            best_move: best_move.map(|(best_move, _)| best_move.to_uci(CastlingMode::Standard).to_string()),
+        })
+    }
+
+    #[cfg(not(feature = "syzygy"))]
+    fn probe_tables(&self, _fen: &str) -> Option<TablebaseProbe> {
+        None
+    }
+}
@@ -0,0 +1,166 @@
+use std::panic;
+#[cfg(all(feature = "godot", not(feature = "embed-tables")))]
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, TryLockError};
+use std::thread;
+
+#[cfg(all(feature = "godot", not(feature = "embed-tables")))]
+use godot::classes::FileAccess;
+#[cfg(feature = "godot")]
+use godot::classes::ProjectSettings;
+use rustier_chess::moves::move_mask_gen::MoveGenMasks;
+use rustier_chess::utils::zobrist::ZobristHasher;
+
+#[cfg(feature = "godot")]
+use crate::consts::ENGINE_MOVE_CACHE_FOLDER_PATH;
+#[cfg(all(feature = "godot", not(feature = "embed-tables")))]
+use crate::consts::ENGINE_MOVE_FOLDER_PATH;
+#[cfg(not(feature = "godot"))]
+use crate::consts::SOURCE_MOVE_FOLDER_PATH;
+
+pub const MOVE_TABLE_NAMES: [&str; 4] = ["king", "knight", "bishop", "rook"];
+
+#[cfg(feature = "embed-tables")]
+const EMBEDDED_MOVE_TABLES: [&[u8]; 4] = [
+    include_bytes!("../../godot/data/moves/king.bin"),
+    include_bytes!("../../godot/data/moves/knight.bin"),
+    include_bytes!("../../godot/data/moves/bishop.bin"),
+    include_bytes!("../../godot/data/moves/rook.bin"),
+];
+
+/// Read-only lookup tables shared by every `ChessEngine` in the process
+pub struct EngineTables {
+    pub hasher: ZobristHasher,
+    pub move_gen_mask: MoveGenMasks,
+}
+
+/// The outcome of the last load, `None` before the first. Held while loading, so nothing loads twice at once.
+static ENGINE_TABLES: Mutex<Option<Result<Arc<EngineTables>, String>>> = Mutex::new(None);
+
+/// Loads the tables on first use, and again after a failed load, e.g. once the files are fixed. Waits for `warm_up`
+/// instead of loading twice if it's still running.
+pub fn get_engine_tables() -> Result<Arc<EngineTables>, String> {
+    get_or_load(|| resolve_move_table_folder().and_then(|folder| load_engine_tables(&folder)))
+}
+
+/// Loads the tables from an OS folder ending in `/` rather than from the Godot project, for running the engine
+/// outside Godot. Does nothing if they are already loaded.
+pub fn load_from_folder(folder: &str) -> Result<(), String> {
+    get_or_load(|| load_engine_tables(folder)).map(|_| ())
+}
+
+/// Starts loading the tables in the background, e.g. while the menu is showing
+pub fn warm_up() {
+    if matches!(*lock_engine_tables(), Some(Ok(_))) {
+        return;
+    }
+    // Godot's file APIs stay on the main thread, the loading thread only reads from the file system
+    match resolve_move_table_folder() {
+        Ok(folder) => {
+            thread::spawn(move || {
+                let _ = get_or_load(|| load_engine_tables(&folder));
+            });
+        }
+        Err(error) => *lock_engine_tables() = Some(Err(error)),
+    }
+}
+
+/// The error of the last load if it failed. Doesn't wait for a load in progress.
+pub fn get_finished_load_error() -> Option<String> {
+    let engine_tables = match ENGINE_TABLES.try_lock() {
+        Ok(engine_tables) => engine_tables,
+        Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+        Err(TryLockError::WouldBlock) => return None,
+    };
+    engine_tables.as_ref()?.as_ref().err().cloned()
+}
+
+/// The loaded tables, or the result of `load` if no load has succeeded yet
+fn get_or_load(load: impl FnOnce() -> Result<Arc<EngineTables>, String>) -> Result<Arc<EngineTables>, String> {
+    let mut engine_tables = lock_engine_tables();
+    if let Some(Ok(tables)) = engine_tables.as_ref() {
+        return Ok(tables.clone());
+    }
+    let result = load();
+    *engine_tables = Some(result.clone());
+    result
+}
+
+fn lock_engine_tables() -> MutexGuard<'static, Option<Result<Arc<EngineTables>, String>>> {
+    // Loading catches the panics of `rustier_chess`, so a poisoned lock still holds a whole result
+    ENGINE_TABLES.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Loads the magic move tables independently of the working directory Godot was started from
+fn load_engine_tables(folder: &str) -> Result<Arc<EngineTables>, String> {
+    // `rustier_chess` panics on unreadable tables, which would otherwise take the whole game down
+    let move_gen_mask = panic::catch_unwind(|| MoveGenMasks::load_from_path(folder))
+        .map_err(|_| format!("Move generation tables in '{}' could not be read", folder))?;
+    Ok(Arc::new(EngineTables {
+        hasher: ZobristHasher::load(),
+        move_gen_mask,
+    }))
+}
+
+/// OS path of a folder holding every table, as `rustier_chess` only reads from the file system
+#[cfg(all(feature = "godot", not(feature = "embed-tables")))]
+fn resolve_move_table_folder() -> Result<String, String> {
+    let folder = globalize_folder(ENGINE_MOVE_FOLDER_PATH);
+    if MOVE_TABLE_NAMES
+        .iter()
+        .all(|name| Path::new(&format!("{}{}.bin", folder, name)).is_file())
+    {
+        return Ok(folder);
+    }
+
+    // Exported builds keep res:// inside the .pck, so copy the tables out to disk
+    write_tables_to_cache(|_, name| {
+        let path = format!("{}{}.bin", ENGINE_MOVE_FOLDER_PATH, name);
+        if !FileAccess::file_exists(&path) {
+            return Err(format!(
+                "Move table '{}' is missing. Exported builds need 'data/moves/*.bin' in the export resource filter.",
+                path
+            ));
+        }
+        Ok(FileAccess::get_file_as_bytes(&path).to_vec())
+    })
+}
+
+#[cfg(feature = "embed-tables")]
+fn resolve_move_table_folder() -> Result<String, String> {
+    write_tables_to_cache(|index, _| Ok(EMBEDDED_MOVE_TABLES[index].to_vec()))
+}
+
+#[cfg(not(feature = "godot"))]
+fn resolve_move_table_folder() -> Result<String, String> {
+    Ok(SOURCE_MOVE_FOLDER_PATH.to_string())
+}
+
+#[cfg(feature = "godot")]
+fn write_tables_to_cache(read_table: impl Fn(usize, &str) -> Result<Vec<u8>, String>) -> Result<String, String> {
+    let folder = globalize_folder(ENGINE_MOVE_CACHE_FOLDER_PATH);
+    std::fs::create_dir_all(&folder).map_err(|error| format!("Could not create '{}': {}", folder, error))?;
+
+    for (index, name) in MOVE_TABLE_NAMES.iter().enumerate() {
+        let bytes = read_table(index, name)?;
+        if bytes.is_empty() {
+            return Err(format!("Move table '{}' is empty", name));
+        }
+        let path = format!("{}{}.bin", folder, name);
+        // Regenerated tables keep their size, so only the same bytes make the copy from an earlier build usable
+        if std::fs::read(&path).is_ok_and(|cached| cached == bytes) {
+            continue;
+        }
+        std::fs::write(&path, bytes).map_err(|error| format!("Could not write '{}': {}", path, error))?;
+    }
+    Ok(folder)
+}
+
+#[cfg(feature = "godot")]
+fn globalize_folder(folder: &str) -> String {
+    let mut output = ProjectSettings::singleton().globalize_path(folder).to_string();
+    if !output.ends_with('/') {
+        output.push('/');
+    }
+    output
+}
@@ -0,0 +1,546 @@
+use std::fmt::Display;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Time the engine gets to answer `uci` and `isready`
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Time past the move's time budget before the engine is told to stop, and again before it's given up on
+const MOVE_GRACE: Duration = Duration::from_secs(1);
+/// Time the engine gets to exit after `quit` before it's killed
+const QUIT_TIMEOUT: Duration = Duration::from_millis(200);
+
+#[derive(Debug)]
+pub enum UciError {
+    Launch(String),
+    /// The process exited or closed its output
+    Crashed(String),
+    Timeout(&'static str),
+}
+
+impl Display for UciError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Launch(reason) => write!(f, "Could not start the engine: {}", reason),
+            Self::Crashed(reason) => write!(f, "The engine stopped: {}", reason),
+            Self::Timeout(stage) => write!(f, "The engine didn't answer in time while {}", stage),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum UciScore {
+    Centipawns(i32),
+    /// Moves until mate, negative when the engine is getting mated
+    Mate(i32),
+}
+
+/// The fields of an `info` line worth showing, all from the point of view of the engine's side
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct UciInfo {
+    pub depth: Option<u32>,
+    pub score: Option<UciScore>,
+    pub nodes: Option<u64>,
+    pub nodes_per_second: Option<u64>,
+    pub pv: Vec<String>,
+}
+
+impl UciInfo {
+    /// Parses the part after `info`. Returns `None` for lines without anything worth showing, like `info string`.
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut info = Self::default();
+        let mut tokens = line.split_whitespace();
+        while let Some(token) = tokens.next() {
+            match token {
+                "depth" => info.depth = tokens.next().and_then(|value| value.parse().ok()),
+                "nodes" => info.nodes = tokens.next().and_then(|value| value.parse().ok()),
+                "nps" => info.nodes_per_second = tokens.next().and_then(|value| value.parse().ok()),
+                "score" => {
+                    let kind = tokens.next();
+                    let value = tokens.next().and_then(|value| value.parse().ok());
+                    info.score = match (kind, value) {
+                        (Some("cp"), Some(value)) => Some(UciScore::Centipawns(value)),
+                        (Some("mate"), Some(value)) => Some(UciScore::Mate(value)),
+                        _ => None,
+                    };
+                }
+                // Everything after `pv` is the variation, and `string` is free text
+                "pv" => info.pv = tokens.by_ref().map(str::to_string).collect(),
+                "string" => break,
+                _ => {}
+            }
+        }
+        (info != Self::default()).then_some(info)
+    }
+}
+
+impl Display for UciInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(depth) = self.depth {
+            parts.push(format!("depth {}", depth));
+        }
+        match self.score {
+            Some(UciScore::Centipawns(centipawns)) => parts.push(format!("{:+.2}", centipawns as f32 / 100.)),
+            Some(UciScore::Mate(moves)) => parts.push(format!("mate {}", moves)),
+            None => {}
+        }
+        if let Some(nodes_per_second) = self.nodes_per_second {
+            parts.push(format!("{} nps", nodes_per_second));
+        }
+        if !self.pv.is_empty() {
+            parts.push(self.pv.join(" "));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// How long the engine may think about a move
+#[derive(Clone, Copy, Debug)]
+pub enum GoLimits {
+    MoveTime(Duration),
+    Clock {
+        white: Duration,
+        black: Duration,
+        white_increment: Duration,
+        black_increment: Duration,
+        white_to_move: bool,
+    },
+}
+
+impl GoLimits {
+    fn to_command(self) -> String {
+        match self {
+            Self::MoveTime(move_time) => format!("go movetime {}", move_time.as_millis()),
+            Self::Clock {
+                white,
+                black,
+                white_increment,
+                black_increment,
+                ..
+            } => format!(
+                "go wtime {} btime {} winc {} binc {}",
+                white.as_millis(),
+                black.as_millis(),
+                white_increment.as_millis(),
+                black_increment.as_millis()
+            ),
+        }
+    }
+
+    /// The most the engine can use on this move
+    fn get_budget(self) -> Duration {
+        match self {
+            Self::MoveTime(move_time) => move_time,
+            Self::Clock {
+                white,
+                black,
+                white_to_move,
+                ..
+            } => {
+                if white_to_move {
+                    white
+                } else {
+                    black
+                }
+            }
+        }
+    }
+}
+
+pub enum UciEvent {
+    Info(UciInfo),
+    /// In UCI notation
+    BestMove(String),
+}
+
+enum State {
+    WaitingForUciOk,
+    WaitingForReadyOk,
+    Ready,
+    Thinking { stop_sent: bool },
+}
+
+/// A UCI engine running as a child process. Commands are written to its stdin and its stdout is read on a thread
+/// of its own, so `poll` never blocks.
+pub struct UciEngine {
+    child: Child,
+    deadline: Option<Instant>,
+    /// Set after stopping a search nobody wants the result of anymore
+    discard_best_move: bool,
+    lines: Receiver<String>,
+    name: Option<String>,
+    /// `position` and `go` commands sent as soon as the engine is ready
+    pending_search: Option<(String, GoLimits)>,
+    state: State,
+    stdin: ChildStdin,
+}
+
+impl UciEngine {
+    pub fn launch(program: &str, args: &[&str]) -> Result<Self, UciError> {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|error| UciError::Launch(format!("{}: {}", program, error)))?;
+        let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            let _ = child.kill();
+            return Err(UciError::Launch("no pipes to the process".to_string()));
+        };
+
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut engine = Self {
+            child,
+            deadline: Some(Instant::now() + HANDSHAKE_TIMEOUT),
+            discard_best_move: false,
+            lines,
+            name: None,
+            pending_search: None,
+            state: State::WaitingForUciOk,
+            stdin,
+        };
+        engine.send("uci")?;
+        Ok(engine)
+    }
+
+    /// The name the engine gave with `id name`
+    pub fn get_name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Starts a search of the position reached by `moves` from `start_fen`, or from the initial position.
+    /// If the engine is still starting up, or busy with a previous search, the search begins once it's ready.
+    pub fn go(&mut self, start_fen: Option<&str>, moves: &[String], limits: GoLimits) -> Result<(), UciError> {
+        let mut position = match start_fen {
+            Some(fen) => format!("position fen {}", fen),
+            None => "position startpos".to_string(),
+        };
+        if !moves.is_empty() {
+            position.push_str(" moves ");
+            position.push_str(&moves.join(" "));
+        }
+        self.pending_search = Some((position, limits));
+        match self.state {
+            State::Ready => self.start_pending_search()?,
+            State::Thinking { .. } => self.stop_search()?,
+            State::WaitingForUciOk | State::WaitingForReadyOk => {}
+        }
+        Ok(())
+    }
+
+    /// Stops the search in progress and drops its result, as well as a search still waiting to start
+    pub fn stop(&mut self) -> Result<(), UciError> {
+        self.pending_search = None;
+        self.stop_search()
+    }
+
+    fn stop_search(&mut self) -> Result<(), UciError> {
+        if matches!(self.state, State::Thinking { .. }) && !self.discard_best_move {
+            self.discard_best_move = true;
+            self.send("stop")?;
+        }
+        Ok(())
+    }
+
+    /// Handles everything the engine wrote since the last call
+    pub fn poll(&mut self) -> Result<Vec<UciEvent>, UciError> {
+        let mut events = Vec::new();
+        loop {
+            match self.lines.try_recv() {
+                Ok(line) => self.handle_line(&line, &mut events)?,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return Err(UciError::Crashed(self.describe_exit())),
+            }
+        }
+        self.check_deadline()?;
+        Ok(events)
+    }
+
+    fn handle_line(&mut self, line: &str, events: &mut Vec<UciEvent>) -> Result<(), UciError> {
+        let (command, rest) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+        match (&self.state, command) {
+            (State::WaitingForUciOk, "id") => {
+                if let Some(name) = rest.strip_prefix("name ") {
+                    self.name = Some(name.trim().to_string());
+                }
+            }
+            (State::WaitingForUciOk, "uciok") => {
+                self.state = State::WaitingForReadyOk;
+                self.send("isready")?;
+            }
+            (State::WaitingForReadyOk, "readyok") => {
+                self.state = State::Ready;
+                self.deadline = None;
+                if self.pending_search.is_some() {
+                    self.start_pending_search()?;
+                }
+            }
+            (State::Thinking { .. }, "info") if self.discard_best_move => {}
+            (State::Thinking { .. }, "info") => events.extend(UciInfo::parse(rest).map(UciEvent::Info)),
+            (State::Thinking { .. }, "bestmove") => {
+                self.state = State::Ready;
+                self.deadline = None;
+                if self.discard_best_move {
+                    self.discard_best_move = false;
+                } else {
+                    let best_move = rest.split_whitespace().next().unwrap_or("(none)");
+                    events.push(UciEvent::BestMove(best_move.to_string()));
+                }
+                self.start_pending_search()?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn start_pending_search(&mut self) -> Result<(), UciError> {
+        let Some((position, limits)) = self.pending_search.take() else {
+            return Ok(());
+        };
+        self.send(&position)?;
+        self.send(&limits.to_command())?;
+        self.state = State::Thinking { stop_sent: false };
+        self.deadline = Some(Instant::now() + limits.get_budget() + MOVE_GRACE);
+        Ok(())
+    }
+
+    fn check_deadline(&mut self) -> Result<(), UciError> {
+        if self.deadline.is_none_or(|deadline| Instant::now() < deadline) {
+            return Ok(());
+        }
+        match self.state {
+            State::WaitingForUciOk | State::WaitingForReadyOk => Err(UciError::Timeout("starting up")),
+            // Ask for the best move so far before giving up
+            State::Thinking { stop_sent: false } => {
+                self.send("stop")?;
+                self.state = State::Thinking { stop_sent: true };
+                self.deadline = Some(Instant::now() + MOVE_GRACE);
+                Ok(())
+            }
+            State::Thinking { stop_sent: true } => Err(UciError::Timeout("thinking")),
+            State::Ready => Ok(()),
+        }
+    }
+
+    fn send(&mut self, command: &str) -> Result<(), UciError> {
+        writeln!(self.stdin, "{}", command)
+            .and_then(|_| self.stdin.flush())
+            .map_err(|error| UciError::Crashed(format!("{} ({})", self.describe_exit(), error)))
+    }
+
+    fn describe_exit(&mut self) -> String {
+        match self.child.try_wait() {
+            Ok(Some(status)) => format!("exited with {}", status),
+            _ => "closed its output".to_string(),
+        }
+    }
+}
+
+impl Drop for UciEngine {
+    fn drop(&mut self) {
+        let _ = writeln!(self.stdin, "quit").and_then(|_| self.stdin.flush());
+        let quit_deadline = Instant::now() + QUIT_TIMEOUT;
+        while Instant::now() < quit_deadline {
+            if !matches!(self.child.try_wait(), Ok(None)) {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    /// Stand-in engine: answers the handshake, then runs `on_go` for every `go`
+    fn launch_scripted(on_go: &str) -> UciEngine {
+        let script = format!(
+            r#"while read -r line; do
+                case "$line" in
+                    uci) echo "id name Scripted"; echo "uciok" ;;
+                    isready) echo "readyok" ;;
+                    go*) {} ;;
+                    quit) exit 0 ;;
+                esac
+            done"#,
+            on_go
+        );
+        UciEngine::launch("sh", &["-c", &script]).unwrap()
+    }
+
+    /// Polls until the engine plays a move or fails, collecting the info lines on the way
+    fn wait_for_move(engine: &mut UciEngine) -> Result<(String, Vec<UciInfo>), UciError> {
+        let mut infos = Vec::new();
+        let give_up = Instant::now() + Duration::from_secs(10);
+        while Instant::now() < give_up {
+            for event in engine.poll()? {
+                match event {
+                    UciEvent::Info(info) => infos.push(info),
+                    UciEvent::BestMove(best_move) => return Ok((best_move, infos)),
+                }
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        panic!("the test engine neither moved nor failed");
+    }
+
+    const MOVE_TIME: GoLimits = GoLimits::MoveTime(Duration::from_millis(50));
+
+    #[test]
+    fn plays_the_best_move_and_reports_info() {
+        let mut engine = launch_scripted(
+            r#"echo "info depth 3 score cp 25 nodes 900 nps 9000 pv e2e4 e7e5"; echo "bestmove e2e4 ponder e7e5""#,
+        );
+        engine.go(None, &[], MOVE_TIME).unwrap();
+        let (best_move, infos) = wait_for_move(&mut engine).unwrap();
+
+        assert_eq!(best_move, "e2e4");
+        assert_eq!(engine.get_name(), Some("Scripted"));
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].depth, Some(3));
+        assert_eq!(infos[0].score, Some(UciScore::Centipawns(25)));
+        assert_eq!(infos[0].pv, vec!["e2e4", "e7e5"]);
+    }
+
+    #[test]
+    fn answers_every_search() {
+        let mut engine = launch_scripted(r#"echo "bestmove g8f6""#);
+        for moves in [vec![], vec!["e2e4".to_string()]] {
+            engine.go(None, &moves, MOVE_TIME).unwrap();
+            assert_eq!(wait_for_move(&mut engine).unwrap().0, "g8f6");
+        }
+    }
+
+    #[test]
+    fn reports_a_crash() {
+        let mut engine = launch_scripted("exit 3");
+        engine.go(None, &[], MOVE_TIME).unwrap();
+        assert!(matches!(wait_for_move(&mut engine), Err(UciError::Crashed(_))));
+    }
+
+    #[test]
+    fn times_out_an_engine_that_never_moves() {
+        let mut engine = launch_scripted(":");
+        engine.go(None, &[], MOVE_TIME).unwrap();
+        assert!(matches!(wait_for_move(&mut engine), Err(UciError::Timeout("thinking"))));
+    }
+
+    #[test]
+    fn moves_after_stop_when_over_time() {
+        let script = r#"while read -r line; do
+            case "$line" in
+                uci) echo "uciok" ;;
+                isready) echo "readyok" ;;
+                stop) echo "bestmove d2d4" ;;
+                quit) exit 0 ;;
+            esac
+        done"#;
+        let mut engine = UciEngine::launch("sh", &["-c", script]).unwrap();
+        engine.go(None, &[], MOVE_TIME).unwrap();
+        assert_eq!(wait_for_move(&mut engine).unwrap().0, "d2d4");
+    }
+
+    #[test]
+    fn drops_the_result_of_a_replaced_search() {
+        // Only answers `stop` on the first search, with a move that must not be played
+        let script = r#"searches=0
+        while read -r line; do
+            case "$line" in
+                uci) echo "uciok" ;;
+                isready) echo "readyok" ;;
+                go*) searches=$((searches + 1)); [ "$searches" -ge 2 ] && echo "bestmove h2h3" ;;
+                stop) echo "bestmove a2a3" ;;
+                quit) exit 0 ;;
+            esac
+        done"#;
+        let mut engine = UciEngine::launch("sh", &["-c", script]).unwrap();
+        engine.go(None, &[], GoLimits::MoveTime(Duration::from_secs(5))).unwrap();
+        while !matches!(engine.state, State::Thinking { .. }) {
+            assert!(engine.poll().unwrap().is_empty());
+            thread::sleep(Duration::from_millis(5));
+        }
+        engine.go(None, &["e2e4".to_string()], MOVE_TIME).unwrap();
+        assert_eq!(wait_for_move(&mut engine).unwrap().0, "h2h3");
+    }
+
+    #[test]
+    fn stop_during_startup_drops_the_queued_search() {
+        let mut engine = launch_scripted(r#"echo "bestmove e2e4""#);
+        engine.go(None, &[], MOVE_TIME).unwrap();
+        engine.stop().unwrap();
+        let give_up = Instant::now() + Duration::from_millis(300);
+        while Instant::now() < give_up {
+            assert!(engine.poll().unwrap().is_empty());
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert!(matches!(engine.state, State::Ready));
+    }
+
+    #[test]
+    fn fails_to_launch_a_missing_binary() {
+        assert!(matches!(
+            UciEngine::launch("/nonexistent/engine", &[]),
+            Err(UciError::Launch(_))
+        ));
+    }
+
+    #[test]
+    fn parses_info_lines() {
+        let info = UciInfo::parse("depth 12 seldepth 18 score mate -3 nodes 123456 nps 1000000 time 123 pv a7a6 b1c3").unwrap();
+        assert_eq!(info.depth, Some(12));
+        assert_eq!(info.score, Some(UciScore::Mate(-3)));
+        assert_eq!(info.nodes, Some(123456));
+        assert_eq!(info.nodes_per_second, Some(1000000));
+        assert_eq!(info.pv, vec!["a7a6", "b1c3"]);
+        assert_eq!(UciInfo::parse("string hello pv e2e4"), None);
+    }
+
+    #[test]
+    fn formats_clock_limits() {
+        let limits = GoLimits::Clock {
+            white: Duration::from_secs(60),
+            black: Duration::from_millis(59_500),
+            white_increment: Duration::from_secs(2),
+            black_increment: Duration::from_secs(2),
+            white_to_move: false,
+        };
+        assert_eq!(limits.to_command(), "go wtime 60000 btime 59500 winc 2000 binc 2000");
+        assert_eq!(limits.get_budget(), Duration::from_millis(59_500));
+    }
+
+    #[test]
+    fn sends_the_moves_played() {
+        // Plays back the last word of the position command, which is the last move played
+        let script = r#"while read -r line; do
+            case "$line" in
+                uci) echo "uciok" ;;
+                isready) echo "readyok" ;;
+                position*) position="$line" ;;
+                go*) echo "bestmove ${position##* }" ;;
+                quit) exit 0 ;;
+            esac
+        done"#;
+        let mut engine = UciEngine::launch("sh", &["-c", script]).unwrap();
+        let moves = ["e2e4".to_string(), "c7c5".to_string()];
+        engine.go(None, &moves, MOVE_TIME).unwrap();
+        assert_eq!(wait_for_move(&mut engine).unwrap().0, "c7c5");
+    }
+}